@@ -1,10 +1,301 @@
-use clap::Parser;
+use anyhow::{anyhow, Result};
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand, ValueEnum};
+use prqlite_rs::{CsvOptions, Prqlite, PrqliteBuilder};
+use serde::Deserialize;
+use std::{
+    ffi::OsString,
+    fs,
+    io::{stderr, stdin, IsTerminal, Read},
+    path::PathBuf,
+    time::Duration,
+};
+
 #[derive(Parser)]
 #[clap(author, version, about)]
 pub struct Args {
-    /// Open database file
-    #[arg(short, long, default_value_t = String::from(":memory:"))]
-    pub open: String,
+    /// Database file to open, takes precedence over --open
+    pub database: Option<String>,
+    /// Open database file, or a `file:` URI such as `file:data.db?mode=ro` [default: :memory:]
+    #[arg(short, long, global = true)]
+    pub open: Option<String>,
+    /// Open the database read-only, `--readonly=false` overrides the config file
+    #[arg(long, global = true, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub readonly: Option<bool>,
+    /// Fail instead of creating the database file when it doesn't exist,
+    /// `--no-create=false` overrides the config file
+    #[arg(long, global = true, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub no_create: Option<bool>,
+    /// Milliseconds to wait for a lock held by another connection
+    #[arg(long, global = true, value_name = "MS")]
+    pub busy_timeout: Option<u64>,
+    /// Interrupt queries running longer than this many milliseconds
+    #[arg(long, global = true, value_name = "MS")]
+    pub timeout: Option<u64>,
+    /// Format of query results: box, ascii, markdown, csv, tsv, json, ndjson, html, line
+    /// or vertical. `export` writes csv, json, ndjson, markdown, html, arrow or parquet
+    /// [default: box, or the extension of the exported file]
+    #[arg(long, global = true)]
+    pub format: Option<String>,
+    /// Color compile errors [default: auto]
+    #[arg(long, global = true, value_enum)]
+    pub color: Option<ColorMode>,
+    /// TOML file giving defaults for the options above, such as `database = "data.db"`
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// Run a PRQL query, or a script of queries, and exit instead of starting the REPL,
+    /// same as the `run` command
+    #[arg(short, long, value_name = "QUERY", conflicts_with = "file")]
+    pub command: Option<String>,
+    /// Run the PRQL script in a file, `-` for stdin, and exit. A script piped to stdin
+    /// is run the same way.
+    #[arg(short, long, value_name = "SCRIPT")]
+    pub file: Option<String>,
+    #[command(subcommand)]
+    pub subcommand: Option<Command>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    /// Color when printing to a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+/// Defaults read from the file given with --config, overridden by the command line.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub database: Option<String>,
+    pub readonly: Option<bool>,
+    pub no_create: Option<bool>,
+    pub busy_timeout: Option<u64>,
+    pub timeout: Option<u64>,
+    pub format: Option<String>,
+    pub color: Option<ColorMode>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Start the interactive shell, the default command
+    Repl,
+    /// Run a PRQL script and print the results of its queries
+    Run(ScriptArgs),
+    /// Compile PRQL into SQL without opening any database
+    Compile(CompileArgs),
+    /// Load a CSV or TSV file into a table of the database, creating it if needed
+    Import(ImportArgs),
+    /// Write the result of a PRQL query to a file, or to stdout with `-`
+    Export(ExportArgs),
+    /// Print the CREATE statements of the tables and views of the database
+    Schema(SchemaArgs),
+    /// Check that the queries of a PRQL script compile and match the database schema,
+    /// without running them
+    Check(ScriptArgs),
+}
+
+#[derive(clap::Args)]
+pub struct ScriptArgs {
+    /// PRQL script file, `-` or nothing for stdin
+    #[arg(conflicts_with = "command")]
+    pub script: Option<String>,
+    /// PRQL query, or script of queries, to use instead of a file
+    #[arg(short, long, value_name = "QUERY")]
+    pub command: Option<String>,
+}
+
+#[derive(clap::Args)]
+pub struct CompileArgs {
+    /// PRQL file, `-` or nothing for stdin
+    pub file: Option<String>,
+    /// Target of the compiler, such as sql.postgres or sql.any
+    #[arg(short, long, default_value = "sql.sqlite")]
+    pub target: String,
+    /// Print the SQL on a single line
+    #[arg(long)]
+    pub no_format: bool,
+}
+
+#[derive(clap::Args)]
+pub struct SchemaArgs {
+    /// Only print this table or view
+    pub table: Option<String>,
+    /// Print the PRQL declarations of the tables instead of SQL
+    #[arg(long)]
+    pub prql: bool,
+}
+
+#[derive(clap::Args)]
+pub struct ImportArgs {
+    /// CSV file to import, read as TSV when its extension is .tsv or .tab
+    pub file: String,
+    /// Table receiving the rows
+    pub table: String,
+    /// Field delimiter, a single ASCII character or `tab`
+    #[arg(short, long, value_parser = parse_ascii)]
+    pub delimiter: Option<u8>,
+    /// Quote character, a single ASCII character
+    #[arg(short, long, value_parser = parse_ascii)]
+    pub quote: Option<u8>,
+    /// The file has no header row, its columns are named column1, column2...
+    #[arg(long)]
+    pub no_header: bool,
+    /// Number of rows read to infer the column types of a new table
+    #[arg(long, value_name = "ROWS")]
+    pub sample_size: Option<usize>,
+}
+
+#[derive(clap::Args)]
+pub struct ExportArgs {
+    /// Output file, `-` for stdout
+    pub file: String,
+    /// PRQL query giving the exported rows
+    pub query: String,
+    /// Encoding of blobs in text formats: base64 or hex
+    #[arg(long, default_value = "base64")]
+    pub blobs: String,
+}
+
+impl ImportArgs {
+    pub fn csv_options(&self) -> CsvOptions {
+        let mut options = CsvOptions::for_path(&self.file);
+        options.has_header(!self.no_header);
+        if let Some(delimiter) = self.delimiter {
+            options.delimiter(delimiter);
+        }
+        if let Some(quote) = self.quote {
+            options.quote(quote);
+        }
+        if let Some(rows) = self.sample_size {
+            options.sample_size(rows);
+        }
+        options
+    }
+}
+
+impl ScriptArgs {
+    /// The script given with --command, or read from the file or stdin.
+    pub fn read(&self) -> std::io::Result<String> {
+        match &self.command {
+            Some(query) => Ok(query.clone()),
+            None => read_script(self.script.as_deref().unwrap_or("-")),
+        }
+    }
+}
+
+impl CompileArgs {
+    /// The PRQL source, read from the file or stdin.
+    pub fn read(&self) -> std::io::Result<String> {
+        read_script(self.file.as_deref().unwrap_or("-"))
+    }
+}
+
+fn parse_ascii(s: &str) -> Result<u8, String> {
+    match s {
+        "tab" | "\\t" => Ok(b'\t'),
+        _ if s.len() == 1 && s.is_ascii() => Ok(s.as_bytes()[0]),
+        _ => Err(format!("'{s}' is not a single ASCII character")),
+    }
+}
+
+impl Args {
+    /// Parse the command line, exiting with a usage error like [`Parser::parse`] does.
+    pub fn parse_checked() -> Self {
+        Self::try_parse_checked(std::env::args_os()).unwrap_or_else(|err| err.exit())
+    }
+
+    /// Parse `args`, also rejecting --command and --file along with a subcommand, which
+    /// clap can't express and which would ignore them.
+    pub fn try_parse_checked<I, T>(args: I) -> std::result::Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args = Self::try_parse_from(args)?;
+        let flag = match (&args.command, &args.file) {
+            (Some(_), _) => "--command",
+            (_, Some(_)) => "--file",
+            _ => return Ok(args),
+        };
+        match &args.subcommand {
+            Some(_) => Err(Self::command().error(
+                ErrorKind::ArgumentConflict,
+                format!("the argument '{flag}' cannot be used with a subcommand, pass the script to `run` instead"),
+            )),
+            None => Ok(args),
+        }
+    }
+
+    /// Fill the options missing from the command line with the ones of the --config file.
+    pub fn apply_config(&mut self) -> Result<()> {
+        let Some(path) = &self.config else {
+            return Ok(());
+        };
+        let text = fs::read_to_string(path)
+            .map_err(|err| anyhow!("cannot read config '{}': {err}", path.display()))?;
+        let config: Config = toml::from_str(&text)
+            .map_err(|err| anyhow!("invalid config '{}': {err}", path.display()))?;
+
+        self.open = self.open.take().or(config.database);
+        self.readonly = self.readonly.or(config.readonly);
+        self.no_create = self.no_create.or(config.no_create);
+        self.busy_timeout = self.busy_timeout.or(config.busy_timeout);
+        self.timeout = self.timeout.or(config.timeout);
+        self.format = self.format.take().or(config.format);
+        self.color = self.color.or(config.color);
+        Ok(())
+    }
+
+    /// Connection settings given on the command line.
+    pub fn prqlite_builder(&self) -> PrqliteBuilder {
+        let path = self.database.as_ref().or(self.open.as_ref());
+        let mut builder = Prqlite::builder();
+        builder
+            .path(path.map_or(":memory:", String::as_str))
+            .read_only(self.readonly.unwrap_or(false))
+            .create(!self.no_create.unwrap_or(false))
+            .color(self.use_color());
+        if let Some(ms) = self.busy_timeout {
+            builder.busy_timeout(Duration::from_millis(ms));
+        }
+        if let Some(ms) = self.timeout {
+            builder.timeout(Duration::from_millis(ms));
+        }
+        builder
+    }
+
+    /// Whether compile errors are colored, which they are in a terminal by default.
+    pub fn use_color(&self) -> bool {
+        match self.color.unwrap_or(ColorMode::Auto) {
+            ColorMode::Auto => stderr().is_terminal(),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+
+    /// The PRQL script to run without the REPL: given with --command or --file, or
+    /// piped to stdin.
+    pub fn script(&self) -> std::io::Result<Option<String>> {
+        if let Some(query) = &self.command {
+            return Ok(Some(query.clone()));
+        }
+        match self.file.as_deref() {
+            Some(path) => read_script(path).map(Some),
+            None if !stdin().is_terminal() => read_script("-").map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Read a script from a file, or from stdin for `-`.
+fn read_script(path: &str) -> std::io::Result<String> {
+    if path != "-" {
+        return fs::read_to_string(path);
+    }
+    let mut script = String::new();
+    stdin().read_to_string(&mut script)?;
+    Ok(script)
 }
 
 // #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Default)]
@@ -23,3 +314,56 @@ pub struct Args {
 //         }
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_conflicts_with_subcommands() {
+        for argv in [
+            &["prqlite", "-c", "from t", "schema"][..],
+            &["prqlite", "--file", "script.prql", "run", "other.prql"],
+        ] {
+            let err = Args::try_parse_checked(argv).err().unwrap();
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+        }
+        let args = Args::try_parse_checked(["prqlite", "data.db", "-c", "from t"]).unwrap();
+        assert_eq!(args.command.as_deref(), Some("from t"));
+        let args = Args::try_parse_checked(["prqlite", "data.db", "run", "-c", "from t"]).unwrap();
+        assert!(args.command.is_none() && args.subcommand.is_some());
+    }
+
+    #[test]
+    fn test_config_overridden_by_flags() {
+        let path = std::env::temp_dir().join(format!("prqlite_config_{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            "readonly = true\nno-create = true\nbusy-timeout = 10\n",
+        )
+        .unwrap();
+        let config = path.to_str().unwrap();
+
+        let mut args = Args::try_parse_checked(["prqlite", "--config", config, "data.db"]).unwrap();
+        args.apply_config().unwrap();
+        assert_eq!((args.readonly, args.no_create), (Some(true), Some(true)));
+        assert_eq!(args.database.as_deref(), Some("data.db"));
+
+        let argv = [
+            "prqlite",
+            "--config",
+            config,
+            "--readonly=false",
+            "--no-create=false",
+        ];
+        let mut args = Args::try_parse_checked(argv).unwrap();
+        args.apply_config().unwrap();
+        assert_eq!((args.readonly, args.no_create), (Some(false), Some(false)));
+        assert_eq!(args.busy_timeout, Some(10));
+
+        let args = Args::try_parse_checked(["prqlite", "--readonly", "schema"]).unwrap();
+        assert_eq!((args.readonly, args.no_create), (Some(true), None));
+        assert!(args.subcommand.is_some());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::{anyhow, Error, Result};
+use arrow_ipc::writer::FileWriter;
+use parquet::arrow::ArrowWriter;
+use prqlite_rs::{
+    arrow_array::RecordBatch, ArrowBatches, BlobEncoding, ExportFormat, ExportOptions, Prqlite,
+};
+use std::{
+    fs::{remove_file, File},
+    io::stdout,
+    path::Path,
+    str::FromStr,
+};
+
+/// File formats query results can be written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    ArrowIpc,
+    Parquet,
+}
+
+/// Formats of `.export` and `prqlite export`: the text formats of the library, plus the
+/// Arrow based file formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text(ExportFormat),
+    File(FileFormat),
+}
+
+impl OutputFormat {
+    /// Guess the format from the extension of `path`.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        extension.parse().map_err(|_| {
+            anyhow!(
+                "unknown file format for '{}', pass the format explicitly.",
+                path.display()
+            )
+        })
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "arrow" | "ipc" | "feather" => Ok(OutputFormat::File(FileFormat::ArrowIpc)),
+            "parquet" => Ok(OutputFormat::File(FileFormat::Parquet)),
+            format => Ok(OutputFormat::Text(format.parse().map_err(|_| {
+                anyhow!(
+                    "unknown format '{s}', use csv, json, ndjson, markdown, html, arrow or parquet."
+                )
+            })?)),
+        }
+    }
+}
+
+/// Run a PRQL query and write its result to `path`, or to stdout when `path` is `-` and
+/// the format is a text one. Returns the number of rows written.
+pub fn export_query(
+    conn: &Prqlite,
+    format: OutputFormat,
+    path: &str,
+    prql: &str,
+    blobs: BlobEncoding,
+) -> Result<usize> {
+    match format {
+        OutputFormat::Text(format) => {
+            let mut options = ExportOptions::new(format);
+            options.blob_encoding(blobs);
+            let mut result = conn.stream(prql)?;
+            let rows = if path == "-" {
+                result.export(&options, stdout().lock())?
+            } else {
+                result.export(&options, File::create(path)?)?
+            };
+            Ok(rows)
+        }
+        OutputFormat::File(_) if path == "-" => Err(anyhow!(
+            "Arrow and Parquet files can't be written to stdout."
+        )),
+        OutputFormat::File(format) => {
+            write_batches(Path::new(path), format, &mut conn.query_arrow(prql, 0)?)
+        }
+    }
+}
+
+fn write_batches(path: &Path, format: FileFormat, batches: &mut ArrowBatches) -> Result<usize> {
+    if let Some(rows) = try_write_batches(path, format, batches)? {
+        return Ok(rows);
+    }
+    // A later batch needed wider column types: start over, finding them first.
+    batches.scan_types(true);
+    try_write_batches(path, format, batches)?
+        .ok_or_else(|| anyhow!("the column types of the result changed while it was written."))
+}
+
+/// Write every batch, or nothing and return `None` when they don't all have the same
+/// schema.
+fn try_write_batches(
+    path: &Path,
+    format: FileFormat,
+    batches: &mut ArrowBatches,
+) -> Result<Option<usize>> {
+    let mut batches = batches.into_iter();
+    let Some(first) = batches.next().transpose()? else {
+        return Err(anyhow!("the query returns no columns."));
+    };
+    let file = File::create(path)?;
+    let written = write_file(file, format, first, batches);
+    // Don't leave a truncated file behind when the query fails midway.
+    if !matches!(written, Ok(Some(_))) {
+        let _ = remove_file(path);
+    }
+    written
+}
+
+fn write_file(
+    file: File,
+    format: FileFormat,
+    first: RecordBatch,
+    batches: impl Iterator<Item = prqlite_rs::Result<RecordBatch>>,
+) -> Result<Option<usize>> {
+    let schema = first.schema();
+    let mut writer = match format {
+        FileFormat::ArrowIpc => BatchWriter::ArrowIpc(FileWriter::try_new(file, &schema)?),
+        FileFormat::Parquet => {
+            BatchWriter::Parquet(ArrowWriter::try_new(file, schema.clone(), None)?)
+        }
+    };
+
+    let mut rows = first.num_rows();
+    writer.write(&first)?;
+    for batch in batches {
+        let batch = batch?;
+        if batch.schema() != schema {
+            return Ok(None);
+        }
+        rows += batch.num_rows();
+        writer.write(&batch)?;
+    }
+    writer.finish()?;
+    Ok(Some(rows))
+}
+
+enum BatchWriter {
+    ArrowIpc(FileWriter<File>),
+    Parquet(ArrowWriter<File>),
+}
+
+impl BatchWriter {
+    fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        match self {
+            BatchWriter::ArrowIpc(writer) => writer.write(batch)?,
+            BatchWriter::Parquet(writer) => writer.write(batch)?,
+        }
+        Ok(())
+    }
+    fn finish(self) -> Result<()> {
+        match self {
+            BatchWriter::ArrowIpc(mut writer) => writer.finish()?,
+            BatchWriter::Parquet(writer) => {
+                writer.close()?;
+            }
+        }
+        Ok(())
+    }
+}
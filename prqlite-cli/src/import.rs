@@ -0,0 +1,33 @@
+use prqlite_rs::ImportReport;
+
+/// Rejected rows listed in an import summary, the others are only counted.
+const LISTED_REJECTIONS: usize = 10;
+
+/// Summarize an import for the user: inserted rows, created columns and rejected rows.
+pub fn import_message(report: &ImportReport) -> String {
+    let mut message = format!(
+        "{} row{} imported into {}",
+        report.inserted,
+        if report.inserted > 1 { "s" } else { "" },
+        report.table
+    );
+    if report.created {
+        let columns = report
+            .columns
+            .iter()
+            .map(|(name, ty)| format!("{name} {ty}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        message += &format!(", table created with ({columns})");
+    }
+    if !report.rejected.is_empty() {
+        message += &format!("\n{} row(s) rejected:", report.rejected.len());
+        for row in report.rejected.iter().take(LISTED_REJECTIONS) {
+            message += &format!("\n  line {}: {}", row.line, row.reason);
+        }
+        if report.rejected.len() > LISTED_REJECTIONS {
+            message += "\n  ...";
+        }
+    }
+    message
+}
@@ -2,8 +2,13 @@
 extern crate lazy_static;
 
 pub mod cli;
+pub mod export;
+pub mod import;
+pub mod render;
 pub mod repl;
+pub mod run;
 mod utils;
 
 pub use cli::*;
 pub use repl::*;
+pub use utils::error_message;
@@ -1,8 +1,102 @@
-use clap::Parser;
-use prqlite_cli::{Args, Repl};
+use prqlite_cli::{
+    error_message,
+    export::{export_query, OutputFormat},
+    import::import_message,
+    render::OutputMode,
+    run::{check_script, compile_prql, run_script, schema_text},
+    Args, Command, Repl,
+};
+use std::path::Path;
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
-    Repl::normal().state(&args.open).build().run().await?;
-    Ok(())
+async fn main() {
+    let mut args = Args::parse_checked();
+    let code = match run(&mut args).await {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("{}", error_message(&err));
+            1
+        }
+    };
+    std::process::exit(code);
+}
+
+/// Run the command given on the command line, returning the exit code of the process.
+async fn run(args: &mut Args) -> anyhow::Result<i32> {
+    args.apply_config()?;
+    let output_mode = || -> anyhow::Result<OutputMode> {
+        Ok(match &args.format {
+            Some(format) => format.parse()?,
+            None => OutputMode::default(),
+        })
+    };
+
+    let command = match args.subcommand.take() {
+        Some(command) => command,
+        None => match args.script()? {
+            Some(script) => {
+                let conn = args.prqlite_builder().open()?;
+                return Ok(run_script(&conn, &script, output_mode()?));
+            }
+            None => Command::Repl,
+        },
+    };
+    match command {
+        Command::Repl => {
+            Repl::normal()
+                .open_state(&args.prqlite_builder())?
+                .output_mode(output_mode()?)
+                .build()
+                .run()
+                .await?
+        }
+        Command::Run(script) => {
+            let script = script.read()?;
+            let conn = args.prqlite_builder().open()?;
+            return Ok(run_script(&conn, &script, output_mode()?));
+        }
+        Command::Check(script) => {
+            let script = script.read()?;
+            let conn = args.prqlite_builder().open()?;
+            return Ok(check_script(&conn, &script));
+        }
+        Command::Compile(compile) => {
+            let sql = compile_prql(
+                &compile.read()?,
+                &compile.target,
+                !compile.no_format,
+                args.use_color(),
+            )?;
+            println!("{sql}");
+        }
+        Command::Schema(schema) => {
+            let conn = args.prqlite_builder().open()?;
+            print!(
+                "{}",
+                schema_text(&conn, schema.table.as_deref(), schema.prql)?
+            );
+        }
+        Command::Import(import) => {
+            let conn = args.prqlite_builder().open()?;
+            let report = conn.import_csv(&import.file, &import.table, &import.csv_options())?;
+            println!("{}", import_message(&report));
+        }
+        Command::Export(export) => {
+            let format = match &args.format {
+                Some(format) => format.parse()?,
+                None => OutputFormat::from_path(Path::new(&export.file))?,
+            };
+            let conn = args.prqlite_builder().open()?;
+            let rows = export_query(
+                &conn,
+                format,
+                &export.file,
+                &export.query,
+                export.blobs.parse()?,
+            )?;
+            if export.file != "-" {
+                println!("{rows} rows exported to {}", export.file);
+            }
+        }
+    }
+    Ok(0)
 }
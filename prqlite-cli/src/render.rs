@@ -0,0 +1,239 @@
+use anyhow::{anyhow, Error, Result};
+use comfy_table::{
+    presets::{ASCII_FULL, UTF8_FULL},
+    ContentArrangement, Table,
+};
+use prqlite_rs::{rusqlite::types::Value, ExportFormat, ExportOptions, QueryResult};
+use std::{
+    fmt::{self, Display, Formatter},
+    io::{stdout, IsTerminal, Write},
+    str::FromStr,
+};
+
+use crate::utils::value_parser;
+
+/// How query results are printed, chosen with `.mode` in the REPL and `--format` on the
+/// command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMode {
+    /// A table drawn with box characters.
+    #[default]
+    Box,
+    /// A table drawn with ASCII characters.
+    Ascii,
+    Markdown,
+    Csv,
+    /// Tab separated values, tabs and line breaks in values being escaped.
+    Tsv,
+    /// A JSON array of objects.
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+    Html,
+    /// One `column = value` line per column, rows separated by a blank line.
+    Line,
+    /// One block per row with a `column | value` line per column.
+    Vertical,
+}
+
+const MODES: &[(&str, OutputMode)] = &[
+    ("box", OutputMode::Box),
+    ("ascii", OutputMode::Ascii),
+    ("markdown", OutputMode::Markdown),
+    ("csv", OutputMode::Csv),
+    ("tsv", OutputMode::Tsv),
+    ("json", OutputMode::Json),
+    ("ndjson", OutputMode::Ndjson),
+    ("html", OutputMode::Html),
+    ("line", OutputMode::Line),
+    ("vertical", OutputMode::Vertical),
+];
+
+impl OutputMode {
+    /// Names of every mode, for help and error messages.
+    pub fn names() -> Vec<&'static str> {
+        MODES.iter().map(|(name, _)| *name).collect()
+    }
+}
+
+impl Display for OutputMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = MODES.iter().find(|(_, mode)| mode == self).unwrap().0;
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for OutputMode {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let lowercase = s.to_ascii_lowercase();
+        let name = match lowercase.as_str() {
+            "table" => "box",
+            "md" => "markdown",
+            "tabs" => "tsv",
+            "jsonl" => "ndjson",
+            "expanded" => "vertical",
+            name => name,
+        };
+        MODES
+            .iter()
+            .find(|(mode, _)| *mode == name)
+            .map(|(_, mode)| *mode)
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown output mode '{s}', use one of: {}.",
+                    Self::names().join(", ")
+                )
+            })
+    }
+}
+
+/// Print the rows of a query result to `out` in the given mode, returning the number of
+/// rows printed.
+pub fn render<W: Write>(result: &mut QueryResult, mode: OutputMode, mut out: W) -> Result<usize> {
+    let rows = match mode {
+        OutputMode::Box | OutputMode::Ascii => {
+            let (table, rows) = result_table(result, mode)?;
+            writeln!(out, "{table}")?;
+            rows
+        }
+        OutputMode::Markdown => result.export(&ExportOptions::new(ExportFormat::Markdown), out)?,
+        OutputMode::Csv => result.export(&ExportOptions::new(ExportFormat::Csv), out)?,
+        OutputMode::Json => result.export(&ExportOptions::new(ExportFormat::Json), out)?,
+        OutputMode::Ndjson => result.export(&ExportOptions::new(ExportFormat::Ndjson), out)?,
+        OutputMode::Html => result.export(&ExportOptions::new(ExportFormat::Html), out)?,
+        OutputMode::Tsv => {
+            let escape = |s: &str| {
+                s.replace('\\', "\\\\")
+                    .replace('\t', "\\t")
+                    .replace('\n', "\\n")
+                    .replace('\r', "\\r")
+            };
+            let names = result
+                .column_names()
+                .iter()
+                .map(|name| escape(name))
+                .collect::<Vec<_>>();
+            writeln!(out, "{}", names.join("\t"))?;
+            let mut rows = 0;
+            for row in result.rows() {
+                let values = row?.iter().map(|v| escape(&text(v))).collect::<Vec<_>>();
+                writeln!(out, "{}", values.join("\t"))?;
+                rows += 1;
+            }
+            rows
+        }
+        OutputMode::Line | OutputMode::Vertical => {
+            let names = result
+                .column_names()
+                .into_iter()
+                .map(str::to_owned)
+                .collect::<Vec<_>>();
+            let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+            let mut rows = 0;
+            for row in result.rows() {
+                let row = row?;
+                match mode {
+                    OutputMode::Line if rows > 0 => writeln!(out)?,
+                    OutputMode::Vertical => writeln!(out, "-[ RECORD {} ]-----", rows + 1)?,
+                    _ => {}
+                }
+                for (name, value) in names.iter().zip(&row) {
+                    match mode {
+                        OutputMode::Line => writeln!(out, "{name:>width$} = {}", text(value))?,
+                        _ => writeln!(out, "{name:<width$} | {}", text(value))?,
+                    }
+                }
+                rows += 1;
+            }
+            rows
+        }
+    };
+    Ok(rows)
+}
+
+/// Render a query result into a string, without the trailing line break.
+pub fn render_to_string(result: &mut QueryResult, mode: OutputMode) -> Result<String> {
+    let mut out = vec![];
+    render(result, mode, &mut out)?;
+    let mut out = String::from_utf8_lossy(&out).into_owned();
+    out.truncate(out.trim_end_matches('\n').len());
+    Ok(out)
+}
+
+/// Values of the text modes: empty for NULL, unlike the tables.
+fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        value => value_parser(value),
+    }
+}
+
+/// Read every row of a query result into a table to display it. The table fits the
+/// terminal when printing to one.
+fn result_table(result: &mut QueryResult, mode: OutputMode) -> Result<(Table, usize)> {
+    let mut table = Table::new();
+    match mode {
+        OutputMode::Ascii => table.load_preset(ASCII_FULL),
+        _ => table.load_preset(UTF8_FULL),
+    };
+    if stdout().is_terminal() {
+        table.set_content_arrangement(ContentArrangement::Dynamic);
+    }
+    table.set_header(result.column_names());
+    let mut rows = 0;
+    for row in result {
+        table.add_row(row?.iter().map(value_parser).collect::<Vec<_>>());
+        rows += 1;
+    }
+    Ok((table, rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prqlite_rs::Prqlite;
+
+    fn rendered(sql: &str, mode: OutputMode) -> String {
+        let conn = Prqlite::open(":memory:").unwrap();
+        let mut result = conn.stream_with_sql(sql).unwrap();
+        render_to_string(&mut result, mode).unwrap()
+    }
+
+    #[test]
+    fn test_tsv_escaping() {
+        let out = rendered(
+            "SELECT 'a\tb' AS \"x\ty\", 'line\nbreak\r' AS text, 'back\\slash' AS path, \
+             x'666f' AS data, NULL AS empty",
+            OutputMode::Tsv,
+        );
+        assert_eq!(
+            out,
+            "x\\ty\ttext\tpath\tdata\tempty\n\
+             a\\tb\tline\\nbreak\\r\tback\\\\slash\tZm8=\t"
+        );
+    }
+
+    #[test]
+    fn test_table_blobs() {
+        for mode in [OutputMode::Box, OutputMode::Ascii] {
+            let out = rendered("SELECT x'666f6f' AS data, NULL AS empty", mode);
+            assert!(out.contains("| Zm9v ") || out.contains("│ Zm9v "), "{out}");
+        }
+    }
+
+    #[test]
+    fn test_line_and_vertical() {
+        let sql = "SELECT 1 AS id, 'ann' AS name, x'666f6f' AS data \
+                   UNION ALL SELECT 2, NULL, NULL";
+        assert_eq!(
+            rendered(sql, OutputMode::Line),
+            "  id = 1\nname = ann\ndata = Zm9v\n\n  id = 2\nname = \ndata = "
+        );
+        assert_eq!(
+            rendered(sql, OutputMode::Vertical),
+            "-[ RECORD 1 ]-----\nid   | 1\nname | ann\ndata | Zm9v\n\
+             -[ RECORD 2 ]-----\nid   | 2\nname | \ndata | "
+        );
+    }
+}
@@ -1,13 +1,23 @@
 use anyhow::{anyhow, Error, Result};
-use comfy_table::{
-    presets::{NOTHING, UTF8_FULL},
-    Cell, ContentArrangement, Table,
+use comfy_table::{presets::NOTHING, Cell, ContentArrangement, Table};
+use prql_compiler::compile;
+use prqlite_rs::{
+    default_compile_options, BackupProgress, BlobEncoding, CsvOptions, FileTableFormat, Prqlite,
+    PrqliteError,
+};
+use std::{
+    fmt::{self, Display, Formatter},
+    io::{stdout, Write as _},
+    path::Path,
+    str::FromStr,
 };
-use prql_compiler::{compile, Options};
-use rusqlite::Statement;
-use std::{cell::RefCell, rc::Rc, str::FromStr, string::ToString};
 
-use crate::{utils::row_value_parser, ReplState};
+use crate::{
+    export::{export_query, OutputFormat},
+    import::import_message,
+    render::{render_to_string, OutputMode},
+    ReplState,
+};
 
 pub trait ExecCommands {
     type Output;
@@ -21,20 +31,85 @@ pub trait ExecSingleCommand {
 pub enum Commands {
     Help,
     Quit,
-    Exit { code: i32 },
-    Compile { input: String },
-    Sql { input: String },
+    Exit {
+        code: i32,
+    },
+    Compile {
+        input: String,
+    },
+    Sql {
+        input: String,
+    },
+    Mode {
+        mode: Option<String>,
+    },
+    /// `.export`, or `.write` when the format is guessed from the file extension.
+    Export {
+        format: Option<String>,
+        path: String,
+        input: String,
+    },
+    Insert {
+        table: String,
+        input: String,
+    },
+    Replace {
+        table: String,
+        input: String,
+    },
+    CreateTable {
+        table: String,
+        input: String,
+    },
+    CreateView {
+        name: String,
+        input: String,
+    },
+    Backup {
+        path: String,
+    },
+    Restore {
+        path: String,
+    },
+    Import {
+        path: String,
+        table: String,
+    },
+    AttachFile {
+        path: String,
+        name: String,
+    },
 }
 
-impl ToString for Commands {
-    fn to_string(&self) -> String {
+impl Display for Commands {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         use Commands::*;
         match self {
-            Quit => "quit".to_owned(),
-            Exit { code } => format!("exit {code}"),
-            Compile { input } => format!("compile {input}"),
-            Sql { input } => format!("sql {input}"),
-            Help => "help".to_owned(),
+            Quit => write!(f, "quit"),
+            Exit { code } => write!(f, "exit {code}"),
+            Compile { input } => write!(f, "compile {input}"),
+            Sql { input } => write!(f, "sql {input}"),
+            Mode { mode: None } => write!(f, "mode"),
+            Mode { mode: Some(mode) } => write!(f, "mode {mode}"),
+            Export {
+                format: None,
+                path,
+                input,
+            } => write!(f, "write {path} {input}"),
+            Export {
+                format: Some(format),
+                path,
+                input,
+            } => write!(f, "export {format} {path} {input}"),
+            Insert { table, input } => write!(f, "insert {table} {input}"),
+            Replace { table, input } => write!(f, "replace {table} {input}"),
+            CreateTable { table, input } => write!(f, "create-table {table} {input}"),
+            CreateView { name, input } => write!(f, "create-view {name} {input}"),
+            Backup { path } => write!(f, "backup {path}"),
+            Restore { path } => write!(f, "restore {path}"),
+            Import { path, table } => write!(f, "import {path} {table}"),
+            AttachFile { path, name } => write!(f, "attach-file {path} as {name}"),
+            Help => write!(f, "help"),
         }
     }
 }
@@ -63,6 +138,17 @@ impl FromStr for Commands {
                     input: args.drain(1..).map(|s| s.to_string() + " ").collect(),
                 })
             }
+            "mode" => {
+                if args.len() > 2 {
+                    return Err(anyhow!(
+                        "you should pass a single output mode, or nothing to show the current one."
+                    ));
+                }
+
+                Ok(Mode {
+                    mode: args.get(1).map(|mode| mode.to_string()),
+                })
+            }
             "sql" => {
                 if args.len() <= 1 {
                     return Err(anyhow!(
@@ -74,6 +160,98 @@ impl FromStr for Commands {
                     input: args.drain(1..).map(|s| s.to_string() + " ").collect(),
                 })
             }
+            "write" => {
+                if args.len() <= 2 {
+                    return Err(anyhow!(
+                        "no args was passed, you should pass the output file and the PRQL query to write its result."
+                    ));
+                }
+
+                let (_, input) = split_words(s, 2);
+                Ok(Export {
+                    format: None,
+                    path: args[1].to_owned(),
+                    input: input.to_owned(),
+                })
+            }
+            "export" => {
+                if args.len() <= 3 {
+                    return Err(anyhow!(
+                        "no args was passed, you should pass the format, the output file and the PRQL query to export its result."
+                    ));
+                }
+
+                let (_, input) = split_words(s, 3);
+                Ok(Export {
+                    format: Some(args[1].to_owned()),
+                    path: args[2].to_owned(),
+                    input: input.to_owned(),
+                })
+            }
+            "insert" | "replace" | "create-table" | "create-view" => {
+                if args.len() <= 2 {
+                    return Err(anyhow!(
+                        "no args was passed, you should pass the table name and the PRQL query giving its rows."
+                    ));
+                }
+
+                // The query keeps its line breaks, which separate PRQL pipeline steps.
+                let (_, input) = split_words(s, 2);
+                let table = args[1].to_owned();
+                let input = input.to_owned();
+                Ok(match args[0] {
+                    "insert" => Insert { table, input },
+                    "replace" => Replace { table, input },
+                    "create-table" => CreateTable { table, input },
+                    _ => CreateView { name: table, input },
+                })
+            }
+            "backup" | "restore" => {
+                if args.len() != 2 {
+                    return Err(anyhow!(
+                        "you should pass the database file to {} as the only argument.",
+                        args[0]
+                    ));
+                }
+
+                let path = args[1].to_owned();
+                Ok(match args[0] {
+                    "backup" => Backup { path },
+                    _ => Restore { path },
+                })
+            }
+            "import" => {
+                if args.len() != 3 {
+                    return Err(anyhow!(
+                        "you should pass the file to import and the table to import it into."
+                    ));
+                }
+
+                Ok(Import {
+                    path: args[1].to_owned(),
+                    table: args[2].to_owned(),
+                })
+            }
+            "attach-file" => {
+                let name = match args[..] {
+                    [_, path] => Path::new(path)
+                        .file_stem()
+                        .and_then(|stem| stem.to_str())
+                        .map(str::to_owned),
+                    [_, _, "as", name] => Some(name.to_owned()),
+                    _ => None,
+                };
+                let Some(name) = name else {
+                    return Err(anyhow!(
+                        "you should pass the file to attach, optionally followed by 'as <NAME>'."
+                    ));
+                };
+
+                Ok(AttachFile {
+                    path: args[1].to_owned(),
+                    name,
+                })
+            }
             "exit" => {
                 if args.len() <= 1 {
                     return Err(anyhow!("no args was passed, you should pass exit code or use '.q' command to exit program with success exit code."));
@@ -97,12 +275,75 @@ impl ExecCommands for Commands {
         match self {
             Commands::Help => Help.exec(),
             Commands::Compile { input } => {
-                Compile::new(input).exec()
+                Compile::new(state.prqlite_conn.borrow().as_ref(), input).exec()
+            }
+            Commands::Sql { input } => {
+                Sql::new(&*state.get_prqlite_conn()?, input, state.output_mode()).exec()
+            }
+            Commands::Mode { mode: None } => Ok(format!(
+                "current output mode: {}\navailable modes: {}",
+                state.output_mode(),
+                OutputMode::names().join(", ")
+            )),
+            Commands::Mode { mode: Some(mode) } => {
+                state.set_output_mode(mode.parse()?);
+                Ok(format!("output mode set to {}", state.output_mode()))
+            }
+            Commands::Export {
+                format,
+                path,
+                input,
+            } => {
+                let format = match format {
+                    Some(format) => format.parse()?,
+                    None => OutputFormat::from_path(Path::new(path))?,
+                };
+                let conn = state.get_prqlite_conn()?;
+                let rows = export_query(&conn, format, path, input, BlobEncoding::default())?;
+                Ok(rows_message(rows, &format!("exported to {path}")))
+            }
+            Commands::Insert { table, input } => {
+                let rows = state.get_prqlite_conn()?.insert_into(table, input)?;
+                Ok(rows_message(rows, "inserted"))
+            }
+            Commands::Replace { table, input } => {
+                let rows = state.get_prqlite_conn()?.replace_into(table, input)?;
+                Ok(rows_message(rows, "inserted or replaced"))
+            }
+            Commands::CreateTable { table, input } => {
+                let rows = state.get_prqlite_conn()?.create_table_as(table, input)?;
+                Ok(format!(
+                    "table {table} created, {}",
+                    rows_message(rows, "inserted")
+                ))
+            }
+            Commands::CreateView { name, input } => {
+                state.get_prqlite_conn()?.create_view(name, input)?;
+                Ok(format!("view {name} created"))
+            }
+            Commands::Backup { path } => {
+                let conn = state.get_prqlite_conn()?;
+                conn.backup_to(path, |progress| print_progress("backup", progress))?;
+                Ok(format!("\ndatabase backed up to {path}"))
+            }
+            Commands::Restore { path } => {
+                let mut conn = state.get_prqlite_conn_mut()?;
+                conn.restore_from(path, |progress| print_progress("restore", progress))?;
+                Ok(format!("\ndatabase restored from {path}"))
+            }
+            Commands::Import { path, table } => {
+                let conn = state.get_prqlite_conn()?;
+                let report = conn.import_csv(path, table, &CsvOptions::for_path(path))?;
+                Ok(import_message(&report))
+            }
+            Commands::AttachFile { path, name } => {
+                let format = FileTableFormat::from_path(path).ok_or_else(|| {
+                    anyhow!("unknown file format for '{path}', use a .csv, .tsv, .json or .ndjson file.")
+                })?;
+                let conn = state.get_prqlite_conn()?;
+                let report = conn.register_file_table(name, path, &format)?;
+                Ok(import_message(&report))
             }
-            Commands::Sql { input } => match state.get_prqlite_conn()?.execute_with_sql(input) {
-                Ok(stmt) => Sql::new(stmt).exec(),
-                Err(err) => Err(anyhow!("{err}")),
-            },
             Commands::Exit { code } => {
                 println!("Program exit with {code}");
                 std::process::exit(*code);
@@ -146,6 +387,71 @@ impl ExecSingleCommand for Help {
                 Cell::new("sql"),
                 Cell::new("<SQL_QUERY>"),
                 Cell::new("Execute SQL query instead of PRQL"),
+            ])
+            .add_row(vec![
+                Cell::new("mode"),
+                Cell::new("[<MODE>]"),
+                Cell::new(
+                    "Print results as box, ascii, markdown, csv, tsv, json, ndjson, html, line or vertical, show the current mode without argument",
+                ),
+            ])
+            .add_row(vec![
+                Cell::new("write"),
+                Cell::new("<FILE> <PRQL_QUERY>"),
+                Cell::new(
+                    "Same as export, with the format given by the file extension",
+                ),
+            ])
+            .add_row(vec![
+                Cell::new("export"),
+                Cell::new("<FORMAT> <FILE> <PRQL_QUERY>"),
+                Cell::new(
+                    "Export the query result as csv, json, ndjson, markdown, html, arrow or parquet, to stdout with '-'",
+                ),
+            ])
+            .add_row(vec![
+                Cell::new("insert"),
+                Cell::new("<TABLE> <PRQL_QUERY>"),
+                Cell::new("Insert the rows of the query into a table"),
+            ])
+            .add_row(vec![
+                Cell::new("replace"),
+                Cell::new("<TABLE> <PRQL_QUERY>"),
+                Cell::new(
+                    "Insert the rows of the query into a table, replacing the rows they conflict with",
+                ),
+            ])
+            .add_row(vec![
+                Cell::new("create-table"),
+                Cell::new("<TABLE> <PRQL_QUERY>"),
+                Cell::new("Create a table holding the rows of the query"),
+            ])
+            .add_row(vec![
+                Cell::new("create-view"),
+                Cell::new("<NAME> <PRQL_QUERY>"),
+                Cell::new("Create a view of the query"),
+            ])
+            .add_row(vec![
+                Cell::new("backup"),
+                Cell::new("<FILE>"),
+                Cell::new("Copy the database into a file while it stays in use"),
+            ])
+            .add_row(vec![
+                Cell::new("restore"),
+                Cell::new("<FILE>"),
+                Cell::new("Replace the database with the content of a file"),
+            ])
+            .add_row(vec![
+                Cell::new("import"),
+                Cell::new("<FILE> <TABLE>"),
+                Cell::new("Load a CSV or TSV file into a table, creating it if needed"),
+            ])
+            .add_row(vec![
+                Cell::new("attach-file"),
+                Cell::new("<FILE> [as <NAME>]"),
+                Cell::new(
+                    "Query a CSV, TSV, JSON or NDJSON file as a temporary table, named after the file by default",
+                ),
             ]);
 
         Ok(format!("{table}"))
@@ -161,45 +467,25 @@ impl ExecSingleCommand for Quit {
 }
 
 struct Sql<'a> {
-    stmt: Rc<RefCell<Statement<'a>>>,
+    conn: &'a Prqlite,
+    input: &'a str,
+    mode: OutputMode,
 }
 impl<'a> Sql<'a> {
-    pub fn new(stmt: Statement<'a>) -> Self {
-        Self { stmt: Rc::new(RefCell::new(stmt)) }
+    pub fn new(conn: &'a Prqlite, input: &'a str, mode: OutputMode) -> Self {
+        Self { conn, input, mode }
     }
 }
 
 impl<'a> ExecSingleCommand for Sql<'a> {
     type Output = String;
     fn exec(&self) -> Result<Self::Output> {
-        let mut table = Table::new();
-        let stmt = Rc::clone(&self.stmt);
-        let mut stmt = stmt.borrow_mut();
-        let column_names = stmt.column_names();
-        let column_count = stmt.column_count();
-
-        if stmt.column_count() > 0 && stmt.readonly() {
-            table
-                .load_preset(UTF8_FULL)
-                .set_content_arrangement(ContentArrangement::Dynamic)
-                .set_width(80)
-                .set_header(column_names);
-
-            let mut rows = stmt.query([]).unwrap();
-
-            while let Some(row) = rows.next().unwrap() {
-                let mut idx = 0;
-                let mut row_content: Vec<String> = vec![];
-
-                while idx < column_count {
-                    row_content.push(row_value_parser(row, idx).unwrap());
-                    idx += 1;
-                }
-                table.add_row(row_content);
-            }
-            Ok(format!("{table}"))
+        let mut result = self.conn.stream_with_sql(self.input)?;
+
+        if !result.column_names().is_empty() && result.readonly() {
+            render_to_string(&mut result, self.mode)
         } else {
-            let effected_rows = stmt.execute([]).unwrap();
+            let effected_rows = result.execute()?;
             Ok(format!(
                 "{effected_rows} row{} effected",
                 if effected_rows > 1 { "s" } else { "" }
@@ -208,37 +494,104 @@ impl<'a> ExecSingleCommand for Sql<'a> {
     }
 }
 
-struct Compile {
-    input: String,
+fn print_progress(action: &str, progress: BackupProgress) {
+    let copied = match progress.pagecount {
+        0 => 1.0,
+        count => (count - progress.remaining) as f64 / count as f64,
+    };
+    print!("\r{action}: {:.0}%", copied * 100.0);
+    let _ = stdout().flush();
 }
 
-impl Compile {
-    pub fn new(input: &str) -> Self {
-        Self {
-            input: input.to_owned(),
-        }
+/// Split the first `n` words off `s`, returning them along with the rest of `s` as is,
+/// apart from the whitespace around it.
+fn split_words(s: &str, n: usize) -> (Vec<&str>, &str) {
+    let mut words = vec![];
+    let mut rest = s.trim_start();
+    while words.len() < n && !rest.is_empty() {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        words.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    (words, rest.trim_end())
+}
+
+fn rows_message(rows: usize, action: &str) -> String {
+    format!("{rows} row{} {action}", if rows > 1 { "s" } else { "" })
+}
+
+struct Compile<'a> {
+    conn: Option<&'a Prqlite>,
+    input: &'a str,
+}
+
+impl<'a> Compile<'a> {
+    pub fn new(conn: Option<&'a Prqlite>, input: &'a str) -> Self {
+        Self { conn, input }
     }
 }
-impl ExecSingleCommand for Compile {
+impl<'a> ExecSingleCommand for Compile<'a> {
     type Output = String;
     fn exec(&self) -> Result<Self::Output> {
-        let opt = Options::default().no_format().no_signature();
-        match compile(&self.input, &opt) {
-            Err(e) => Err(anyhow!("Cannot compile your query into SQL: \n{e}")),
-            Ok(sql) => Ok(format!(
-                "{}",
-                sql.replace("\n", " ")
-                    .split_whitespace()
-                    .filter_map(|e| {
-                        if e.is_empty() {
-                            return None;
-                        }
-                        let mut e = e.to_string();
-                        e.push_str(" ");
-                        Some(e)
-                    })
-                    .collect::<String>(),
-            )),
+        // Compile like the queries are run when a database is open, with its schema and
+        // functions declared.
+        let (sql, format) = match self.conn {
+            Some(conn) => (conn.compile(self.input)?, conn.compile_options().format),
+            None => {
+                let options = default_compile_options();
+                let sql = compile(self.input, &options).map_err(PrqliteError::from)?;
+                (sql, options.format)
+            }
+        };
+        match format {
+            true => Ok(sql),
+            false => Ok(sql
+                .replace('\n', " ")
+                .split_whitespace()
+                .filter_map(|e| {
+                    if e.is_empty() {
+                        return None;
+                    }
+                    let mut e = e.to_string();
+                    e.push(' ');
+                    Some(e)
+                })
+                .collect::<String>()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multiline_queries() {
+        let command = "insert big  from orders\nfilter name == \"a  b\"\n";
+        let Ok(Commands::Insert { table, input }) = command.parse() else {
+            panic!("expected an insert command");
+        };
+        assert_eq!(table, "big");
+        assert_eq!(input, "from orders\nfilter name == \"a  b\"");
+
+        let Ok(Commands::Export {
+            format,
+            path,
+            input,
+        }) = "export csv out.csv from orders\nselect [id]".parse()
+        else {
+            panic!("expected an export command");
+        };
+        assert_eq!((format.as_deref(), path.as_str()), (Some("csv"), "out.csv"));
+        assert_eq!(input, "from orders\nselect [id]");
+        let Ok(Commands::Export {
+            format: None,
+            input,
+            ..
+        }) = "write out.parquet from orders\nselect [id]".parse::<Commands>()
+        else {
+            panic!("expected a write command");
+        };
+        assert_eq!(input, "from orders\nselect [id]");
+    }
+}
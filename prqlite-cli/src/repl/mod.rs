@@ -3,10 +3,16 @@ mod consts;
 mod normal;
 mod traits;
 
+use std::{
+    cell::{Cell, Ref, RefCell, RefMut},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
-use std::str::FromStr;
-
-use crate::utils::row_value_parser;
+use crate::render::{render_to_string, OutputMode};
 
 use commands::Commands;
 use commands::ExecCommands;
@@ -14,10 +20,7 @@ use normal::*;
 use traits::*;
 
 use anyhow::{anyhow, Result};
-use comfy_table::presets::UTF8_FULL;
-use comfy_table::ContentArrangement;
-use comfy_table::Table;
-use prqlite_rs::Prqlite;
+use prqlite_rs::{Prqlite, PrqliteBuilder};
 
 const DEFAULT_PROMPT: &str = ">";
 const DEFAULT_COMMAND_PREFIX: &str = ".";
@@ -42,15 +45,18 @@ pub struct ReplBuilder {
     command_prefix: Option<String>,
     mode: Option<ReplMode>,
     state: Option<ReplState>,
+    output_mode: Option<OutputMode>,
 }
 
 impl<'a> Repl<'a> {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new() -> ReplBuilder {
         ReplBuilder {
             prompt: None,
             command_prefix: None,
             mode: None,
             state: None,
+            output_mode: None,
         }
     }
     pub fn normal() -> ReplBuilder {
@@ -59,10 +65,12 @@ impl<'a> Repl<'a> {
             command_prefix: None,
             mode: Some(ReplMode::Normal),
             state: None,
+            output_mode: None,
         }
     }
 
     pub async fn run(&self) -> Result<()> {
+        self.cancel_on_ctrl_c();
         match self.mode {
             ReplMode::Normal => {
                 NormalRepl::new(&self.prompt, &self.command_prefix, self.state).run()
@@ -71,6 +79,26 @@ impl<'a> Repl<'a> {
     }
 }
 
+impl Repl<'_> {
+    /// Interrupt the running query on Ctrl-C, and exit when no query is running.
+    fn cancel_on_ctrl_c(&self) {
+        let handle = self
+            .state
+            .get_prqlite_conn()
+            .ok()
+            .map(|conn| conn.interrupt_handle());
+        let running = self.state.running.clone();
+        tokio::spawn(async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                match (&handle, running.load(Ordering::SeqCst)) {
+                    (Some(handle), true) => handle.interrupt(),
+                    _ => std::process::exit(130),
+                }
+            }
+        });
+    }
+}
+
 impl ReplBuilder {
     pub fn prompt(&mut self, prompt: &str) -> &mut Self {
         self.prompt = Some(prompt.to_string());
@@ -86,7 +114,20 @@ impl ReplBuilder {
         self.state = Some(repl_state);
         self
     }
-    pub fn build(&self) -> Repl {
+    pub fn open_state(&mut self, builder: &PrqliteBuilder) -> Result<&mut Self> {
+        self.state = Some(ReplState::open(builder)?);
+        Ok(self)
+    }
+    /// How query results are printed until changed with `.mode`.
+    pub fn output_mode(&mut self, mode: OutputMode) -> &mut Self {
+        self.output_mode = Some(mode);
+        self
+    }
+    pub fn build(&self) -> Repl<'_> {
+        let state = self.state.as_ref().unwrap();
+        if let Some(mode) = self.output_mode {
+            state.set_output_mode(mode);
+        }
         Repl {
             prompt: self.prompt.clone().unwrap_or(DEFAULT_PROMPT.to_string()),
             mode: self.mode.unwrap_or_default(),
@@ -94,33 +135,61 @@ impl ReplBuilder {
                 .command_prefix
                 .clone()
                 .unwrap_or(DEFAULT_COMMAND_PREFIX.to_string()),
-            state: self.state.as_ref().unwrap(),
+            state,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct ReplState {
-    pub prqlite_conn: Option<Prqlite>,
+    pub prqlite_conn: RefCell<Option<Prqlite>>,
+    /// Whether a query is running, in which case Ctrl-C interrupts it.
+    running: Arc<AtomicBool>,
+    output_mode: Cell<OutputMode>,
 }
-impl<'a> ReplState {
+impl ReplState {
     pub fn new() -> Self {
-        ReplState { prqlite_conn: None }
+        ReplState::default()
+    }
+    pub fn open(builder: &PrqliteBuilder) -> Result<Self> {
+        Ok(ReplState {
+            prqlite_conn: RefCell::new(Some(builder.open()?)),
+            ..Default::default()
+        })
     }
     pub fn set_conn(&mut self, path: &str) -> Result<&mut Self> {
-        self.prqlite_conn = Some(Prqlite::open(path)?);
+        self.prqlite_conn = RefCell::new(Some(Prqlite::open(path)?));
         Ok(self)
     }
-    pub fn get_prqlite_conn(&self) -> Result<&Prqlite> {
-        if let Some(conn) = self.prqlite_conn.as_ref() {
-            return Ok(conn);
-        }
-        Err(anyhow!(
-            "Didn't connected with database, please restart program with '--open <DATABASE_FILE>' flag."
-        ))
+    /// Run `f`, letting Ctrl-C interrupt the queries it runs.
+    pub fn interruptible<T>(&self, f: impl FnOnce() -> T) -> T {
+        self.running.store(true, Ordering::SeqCst);
+        let out = f();
+        self.running.store(false, Ordering::SeqCst);
+        out
+    }
+    pub fn output_mode(&self) -> OutputMode {
+        self.output_mode.get()
+    }
+    pub fn set_output_mode(&self, mode: OutputMode) {
+        self.output_mode.set(mode);
+    }
+    pub fn get_prqlite_conn(&self) -> Result<Ref<'_, Prqlite>> {
+        Ref::filter_map(self.prqlite_conn.borrow(), Option::as_ref).map_err(|_| not_connected())
+    }
+    /// The connection borrowed exclusively, e.g. to restore a backup into it.
+    pub fn get_prqlite_conn_mut(&self) -> Result<RefMut<'_, Prqlite>> {
+        RefMut::filter_map(self.prqlite_conn.borrow_mut(), Option::as_mut)
+            .map_err(|_| not_connected())
     }
 }
 
+fn not_connected() -> anyhow::Error {
+    anyhow!(
+        "Didn't connected with database, please restart program with '--open <DATABASE_FILE>' flag."
+    )
+}
+
 pub struct ReplInputEvent<'a> {
     pub state: &'a ReplState,
 }
@@ -131,46 +200,16 @@ impl<'a> ReplInputEvent<'a> {
     }
     pub fn on_command(&self, buf: &str) -> Result<String> {
         match Commands::from_str(&buf[1..]) {
-            Err(e) => return Err(e),
+            Err(e) => Err(e),
             Ok(cmd) => match cmd.exec(self.state) {
                 Ok(out) => Ok(out),
-                Err(e) => return Err(e),
+                Err(e) => Err(e),
             },
         }
     }
     pub fn on_regular_input(&self, buf: &str) -> Result<String> {
-        let conn = self.state.get_prqlite_conn();
-        if let Err(err) = conn {
-            return Err(err);
-        }
-        match conn.unwrap().execute(buf) {
-            Ok(stmt) => {
-                let mut table = Table::new();
-                let mut stmt = stmt;
-                let column_names = stmt.column_names();
-                let column_count = stmt.column_count();
-
-                table
-                    .load_preset(UTF8_FULL)
-                    .set_content_arrangement(ContentArrangement::Dynamic)
-                    .set_width(80)
-                    .set_header(column_names);
-
-                let mut rows = stmt.query([]).unwrap();
-
-                while let Some(row) = rows.next()? {
-                    let mut idx = 0;
-                    let mut row_content: Vec<String> = vec![];
-
-                    while idx < column_count {
-                        row_content.push(row_value_parser(row, idx).unwrap());
-                        idx += 1;
-                    }
-                    table.add_row(row_content);
-                }
-                Ok(table.lines().collect::<Vec<String>>().join("\n"))
-            }
-            Err(err) => Err(err),
-        }
+        let conn = self.state.get_prqlite_conn()?;
+        let mut result = conn.stream(buf)?;
+        render_to_string(&mut result, self.state.output_mode())
     }
 }
@@ -1,4 +1,4 @@
-use crate::{ReplInputEvent, ReplState};
+use crate::{utils::error_message, ReplInputEvent, ReplState};
 
 use super::{consts::PRQLITE_VERSION, traits::Runner};
 use anyhow::Result;
@@ -63,13 +63,14 @@ impl<'a> Runner for NormalRepl<'a> {
                 .to_owned()
                 .replacen(";", "", buf.rfind(";").unwrap());
 
-            let exec_output = match buf.trim().starts_with(&self.command_prefix) {
+            let is_command = buf.trim().starts_with(&self.command_prefix);
+            let exec_output = self.state.interruptible(|| match is_command {
                 true => repl_input_event.on_command(&buf),
                 false => repl_input_event.on_regular_input(&buf),
-            };
+            });
 
             if let Err(err) = exec_output {
-                eprintln!("\x1b[93m{}\x1b[0m", err.to_string());
+                eprintln!("\x1b[93m{}\x1b[0m", error_message(&err));
             } else {
                 println!("{}", exec_output.unwrap());
             }
@@ -86,8 +87,8 @@ pub fn read_input(stdout: &mut Stdout, buf: &mut String, prompt: String) -> Resu
     while let Event::Key(KeyEvent { code, .. }) = read()? {
         match code {
             KeyCode::Enter => {
-                if buf.trim().chars().last().unwrap() != ';' {
-                    buf.push_str("\n");
+                if !buf.trim().ends_with(';') {
+                    buf.push('\n');
                     print!("..~");
                     stdout.flush()?;
                     continue;
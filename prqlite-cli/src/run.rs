@@ -0,0 +1,147 @@
+use anyhow::{anyhow, Error, Result};
+use prql_compiler::{compile, Options, Target};
+use prqlite_rs::{schema_prelude, split_queries, Prqlite, PrqliteError};
+use std::{io::stdout, str::FromStr};
+
+use crate::{
+    render::{render, OutputMode},
+    utils::error_message,
+};
+
+/// Run every query of a PRQL script in order, printing their results to stdout. Stops at
+/// the first failing query, printing its error to stderr, and returns the exit code of
+/// the process: `0` on success, `1` on failure.
+pub fn run_script(conn: &Prqlite, script: &str, mode: OutputMode) -> i32 {
+    match try_run_script(conn, script, mode) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{}", error_message(&err));
+            1
+        }
+    }
+}
+
+fn try_run_script(conn: &Prqlite, script: &str, mode: OutputMode) -> Result<()> {
+    let queries = split_queries(script);
+    let single = queries.len() == 1;
+    for (index, query) in queries.iter().enumerate() {
+        print_query(conn, &query.source, mode).map_err(|err| {
+            match err.downcast::<PrqliteError>() {
+                Ok(err) if !single => Error::new(PrqliteError::Batch {
+                    index,
+                    start_line: query.start_line,
+                    end_line: query.end_line,
+                    source: Box::new(err),
+                }),
+                Ok(err) => Error::new(err),
+                Err(err) => err,
+            }
+        })?;
+    }
+    Ok(())
+}
+
+fn print_query(conn: &Prqlite, prql: &str, mode: OutputMode) -> Result<()> {
+    render(&mut conn.stream(prql)?, mode, stdout().lock())?;
+    Ok(())
+}
+
+/// Compile every query of a PRQL script and prepare its SQL against the database without
+/// running it, printing the failures to stderr. Returns the exit code of the process:
+/// `0` when every query is valid, `1` otherwise.
+pub fn check_script(conn: &Prqlite, script: &str) -> i32 {
+    let queries = split_queries(script);
+    let mut failed = 0;
+    for (index, query) in queries.iter().enumerate() {
+        if let Err(err) = conn.stream(&query.source) {
+            failed += 1;
+            let err = Error::new(PrqliteError::Batch {
+                index,
+                start_line: query.start_line,
+                end_line: query.end_line,
+                source: Box::new(err),
+            });
+            eprintln!("{}", error_message(&err));
+        }
+    }
+    println!(
+        "{} quer{} checked, {failed} failed",
+        queries.len(),
+        if queries.len() == 1 { "y" } else { "ies" }
+    );
+    i32::from(failed > 0)
+}
+
+/// Compile PRQL into SQL for `target`, without any database.
+pub fn compile_prql(prql: &str, target: &str, format: bool, color: bool) -> Result<String> {
+    let target = Target::from_str(target).map_err(|_| {
+        anyhow!(
+            "unknown target '{target}', use one of: {}.",
+            Target::names().join(", ")
+        )
+    })?;
+    let options = Options::default()
+        .with_target(target)
+        .with_color(color)
+        .no_signature();
+    let options = if format { options } else { options.no_format() };
+    compile(prql, &options).map_err(|err| PrqliteError::from(err).into())
+}
+
+/// CREATE statements of the tables and views of the database, or PRQL declarations of
+/// its tables, optionally limited to `table`.
+pub fn schema_text(conn: &Prqlite, table: Option<&str>, prql: bool) -> Result<String> {
+    let mut schema = conn.schema()?;
+    if let Some(name) = table {
+        for database in &mut schema.databases {
+            database
+                .tables
+                .retain(|t| t.name.eq_ignore_ascii_case(name));
+        }
+        if schema.databases.iter().all(|db| db.tables.is_empty()) {
+            return Err(anyhow!("no such table: {name}"));
+        }
+    }
+    if prql {
+        return Ok(schema_prelude(&schema));
+    }
+    let statements = schema
+        .tables()
+        .filter_map(|(_, table)| table.sql.as_deref())
+        .map(|sql| format!("{sql};\n"))
+        .collect();
+    Ok(statements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_script_failing_query() {
+        let conn = Prqlite::open(":memory:").unwrap();
+        conn.execute_batch_with_sql("CREATE TABLE t (a integer); INSERT INTO t VALUES (1);")
+            .unwrap();
+        let script = "from t\n\nfrom nowhere\nselect [a]\n\nfrom t";
+
+        let err = try_run_script(&conn, script, OutputMode::Csv).unwrap_err();
+        match err.downcast_ref::<PrqliteError>() {
+            Some(PrqliteError::Batch {
+                index: 1,
+                start_line: 3,
+                end_line: 4,
+                source,
+            }) => assert!(matches!(**source, PrqliteError::Sqlite { .. })),
+            err => panic!("unexpected error: {err:?}"),
+        }
+        assert_eq!(run_script(&conn, script, OutputMode::Csv), 1);
+
+        // A single query is reported as is.
+        let err = try_run_script(&conn, "from nowhere", OutputMode::Csv).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PrqliteError>(),
+            Some(PrqliteError::Sqlite { .. })
+        ));
+        assert_eq!(run_script(&conn, "from t", OutputMode::Csv), 0);
+    }
+}
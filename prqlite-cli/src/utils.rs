@@ -1,17 +1,57 @@
-use anyhow::Result;
+use anyhow::Error;
 /// Random public functions used in different parts
-use rusqlite::{types::ValueRef::*, Row};
-use std::str::from_utf8;
+use prqlite_rs::{BlobEncoding, PrqliteError};
+use rusqlite::types::Value::{self, *};
 
 /// Parse Sqlite value into string to display it.
-pub fn row_value_parser(row: &Row, idx: usize) -> Result<String> {
-    let column_type = row.get_ref_unwrap(idx);
-    let out: String = match column_type {
+pub fn value_parser(value: &Value) -> String {
+    match value {
         Null => "-".to_owned(),
         Integer(v) => v.to_string(),
-        Blob(v) => format!("{:?}", v),
-        Text(v) => from_utf8(v).unwrap().to_owned(),
+        Blob(v) => BlobEncoding::default().encode(v),
+        Text(v) => v.clone(),
         Real(v) => v.to_string(),
-    };
-    Ok(out)
+    }
+}
+
+/// Render an error for the user, describing where it came from when it's a Prqlite error.
+pub fn error_message(err: &Error) -> String {
+    match err.downcast_ref::<PrqliteError>() {
+        Some(err) => prqlite_error_message(err),
+        None => err.to_string(),
+    }
+}
+
+fn prqlite_error_message(err: &PrqliteError) -> String {
+    match err {
+        PrqliteError::Compile(messages) => {
+            format!("Cannot compile your query into SQL: \n{messages}")
+        }
+        err if err.is_interrupted() => "Query interrupted.".to_owned(),
+        PrqliteError::Batch {
+            index,
+            start_line,
+            end_line,
+            source,
+        } => format!(
+            "Query #{} (lines {start_line}-{end_line}) failed. {}",
+            index + 1,
+            prqlite_error_message(source)
+        ),
+        PrqliteError::Sqlite {
+            extended_code,
+            source,
+            ..
+        } => {
+            let mut out = match extended_code {
+                Some(code) => format!("SQLite error (code {code}): {source}"),
+                None => format!("SQLite error: {source}"),
+            };
+            if let Some(location) = err.origin().and_then(|o| o.prql_location.as_ref()) {
+                out.push_str(&format!("\n{}", location.caret()));
+            }
+            out
+        }
+        err => err.to_string(),
+    }
 }
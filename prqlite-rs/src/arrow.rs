@@ -0,0 +1,295 @@
+use crate::{Prqlite, PrqliteError, QueryResult, QueryRows, Result};
+use arrow_array::{
+    builder::{BinaryBuilder, Float64Builder, Int64Builder, StringBuilder},
+    ArrayRef, RecordBatch,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use rusqlite::types::{Type, Value};
+use std::sync::Arc;
+
+const DEFAULT_BATCH_SIZE: usize = 1024;
+
+impl Prqlite {
+    /// Run a PRQL query and read its result as Arrow record batches of up to
+    /// `batch_size` rows.
+    ///
+    /// ```
+    /// # fn main() -> prqlite_rs::Result<()> {
+    /// # let conn = prqlite_rs::Prqlite::open(":memory:")?;
+    /// # conn.execute_batch_with_sql("CREATE TABLE persons (id integer)")?;
+    /// for batch in &mut conn.query_arrow("from persons", 1024)? {
+    ///     let batch = batch?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn query_arrow(&self, prql: &str, batch_size: usize) -> Result<ArrowBatches<'_>> {
+        Ok(self.stream(prql)?.into_arrow(batch_size))
+    }
+}
+
+impl<'conn> QueryResult<'conn> {
+    /// Read the result as Arrow record batches of up to `batch_size` rows.
+    pub fn into_arrow(self, batch_size: usize) -> ArrowBatches<'conn> {
+        ArrowBatches {
+            result: self,
+            batch_size: match batch_size {
+                0 => DEFAULT_BATCH_SIZE,
+                size => size,
+            },
+            scan_types: false,
+        }
+    }
+}
+
+/// A query whose result is read as Arrow record batches, created by
+/// [`Prqlite::query_arrow`]. Iterating over `&mut ArrowBatches` runs the query.
+///
+/// SQLite columns may hold values of any type, so the type of each column starts from
+/// the declared type of the table column, following SQLite's type affinity rules: `INT`
+/// columns are `Int64`, `CHAR`, `CLOB` and `TEXT` ones are `Utf8`, `REAL`, `FLOA` and
+/// `DOUB` ones are `Float64`, and `BLOB` ones `Binary`. It is then widened by the values
+/// of every batch read so far: `Int64` to `Float64` for reals, to `Binary` when there are
+/// blobs, and to `Utf8` for any other mix. Columns without a declared type nor values are
+/// `Utf8`. Every field is nullable.
+///
+/// A batch holding values that don't fit the types of the previous ones thus comes with a
+/// wider schema. Use [`ArrowBatches::scan_types`] when every batch must share one schema.
+pub struct ArrowBatches<'conn> {
+    result: QueryResult<'conn>,
+    batch_size: usize,
+    scan_types: bool,
+}
+
+impl ArrowBatches<'_> {
+    /// Run the query once before reading the batches to find the types holding every
+    /// value, so that all batches have the same schema. This doubles the cost of the
+    /// query, and rows changed between the two runs may still widen the schema.
+    pub fn scan_types(&mut self, scan_types: bool) -> &mut Self {
+        self.scan_types = scan_types;
+        self
+    }
+
+    /// Read every batch.
+    pub fn collect_batches(mut self) -> Result<Vec<RecordBatch>> {
+        (&mut self).into_iter().collect()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut ArrowBatches<'_> {
+    type Item = Result<RecordBatch>;
+    type IntoIter = RecordBatches<'a>;
+    fn into_iter(self) -> RecordBatches<'a> {
+        let columns = self.result.columns();
+        let names: Vec<String> = columns.iter().map(|column| column.name.clone()).collect();
+        let mut types: Vec<Option<DataType>> = columns
+            .iter()
+            .map(|column| column.decl_type.as_deref().and_then(declared_type))
+            .collect();
+        let error = match self.scan_types {
+            true => self
+                .result
+                .scan_types(|idx, value| types[idx] = widen(types[idx].take(), value))
+                .err(),
+            false => None,
+        };
+        RecordBatches {
+            schema: result_schema(&names, &types),
+            names,
+            types,
+            error,
+            batch_size: self.batch_size,
+            rows: self.result.rows(),
+            emitted: false,
+            done: false,
+        }
+    }
+}
+
+/// Iterator over the record batches of an [`ArrowBatches`].
+pub struct RecordBatches<'a> {
+    /// Schema of the last batch, made of the column `names` and `types`.
+    schema: SchemaRef,
+    names: Vec<String>,
+    types: Vec<Option<DataType>>,
+    /// Failure to scan the column types, returned by the first call to `next`.
+    error: Option<PrqliteError>,
+    batch_size: usize,
+    rows: QueryRows<'a>,
+    emitted: bool,
+    done: bool,
+}
+
+impl RecordBatches<'_> {
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        let mut rows = Vec::with_capacity(self.batch_size);
+        for row in self.rows.by_ref() {
+            rows.push(row?);
+            if rows.len() == self.batch_size {
+                break;
+            }
+        }
+        // A result without rows still has a schema, returned as one empty batch.
+        if rows.is_empty() && (self.emitted || self.schema.fields().is_empty()) {
+            return Ok(None);
+        }
+        if rows.len() < self.batch_size {
+            self.done = true;
+        }
+        self.emitted = true;
+
+        let mut widened = false;
+        for (idx, current) in self.types.iter_mut().enumerate() {
+            for row in &rows {
+                let ty = widen(current.clone(), row[idx].data_type());
+                widened |= ty != *current;
+                *current = ty;
+            }
+        }
+        if widened {
+            self.schema = result_schema(&self.names, &self.types);
+        }
+
+        let arrays = self
+            .schema
+            .fields()
+            .iter()
+            .enumerate()
+            .map(|(idx, field)| build_array(field, rows.iter().map(|row| &row[idx])))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(RecordBatch::try_new_with_options(
+            self.schema.clone(),
+            arrays,
+            &arrow_array::RecordBatchOptions::new().with_row_count(Some(rows.len())),
+        )?))
+    }
+}
+
+impl Iterator for RecordBatches<'_> {
+    type Item = Result<RecordBatch>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let batch = self.next_batch();
+        if !matches!(batch, Ok(Some(_))) {
+            self.done = true;
+        }
+        batch.transpose()
+    }
+}
+
+/// Arrow type of a column declared with `decl_type`, following SQLite's rules to
+/// determine column affinity. `None` for untyped columns and `NUMERIC` affinity, whose
+/// values may be of any type.
+fn declared_type(decl_type: &str) -> Option<DataType> {
+    let decl_type = decl_type.to_ascii_uppercase();
+    if decl_type.contains("INT") {
+        Some(DataType::Int64)
+    } else if ["CHAR", "CLOB", "TEXT"]
+        .iter()
+        .any(|t| decl_type.contains(t))
+    {
+        Some(DataType::Utf8)
+    } else if decl_type.contains("BLOB") {
+        Some(DataType::Binary)
+    } else if ["REAL", "FLOA", "DOUB"]
+        .iter()
+        .any(|t| decl_type.contains(t))
+    {
+        Some(DataType::Float64)
+    } else {
+        None
+    }
+}
+
+/// Arrow type able to hold the values of a column of type `current` along with `value`.
+fn widen(current: Option<DataType>, value: Type) -> Option<DataType> {
+    match (current, value) {
+        (current, Type::Null) => current,
+        (None | Some(DataType::Int64), Type::Integer) => Some(DataType::Int64),
+        (None | Some(DataType::Int64 | DataType::Float64), Type::Integer | Type::Real) => {
+            Some(DataType::Float64)
+        }
+        (Some(DataType::Binary), _) | (_, Type::Blob) => Some(DataType::Binary),
+        _ => Some(DataType::Utf8),
+    }
+}
+
+fn result_schema(names: &[String], types: &[Option<DataType>]) -> SchemaRef {
+    let fields: Vec<Field> = names
+        .iter()
+        .zip(types)
+        .map(|(name, data_type)| {
+            Field::new(name, data_type.clone().unwrap_or(DataType::Utf8), true)
+        })
+        .collect();
+    Arc::new(Schema::new(fields))
+}
+
+fn build_array<'a>(
+    field: &Field,
+    values: impl Iterator<Item = &'a Value>,
+) -> Result<ArrayRef, ArrowError> {
+    let mismatch = |value: &Value| {
+        ArrowError::CastError(format!(
+            "cannot store {value:?} in column '{}' of type {}",
+            field.name(),
+            field.data_type()
+        ))
+    };
+    Ok(match field.data_type() {
+        DataType::Int64 => {
+            let mut builder = Int64Builder::new();
+            for value in values {
+                match value {
+                    Value::Null => builder.append_null(),
+                    Value::Integer(v) => builder.append_value(*v),
+                    value => return Err(mismatch(value)),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Float64 => {
+            let mut builder = Float64Builder::new();
+            for value in values {
+                match value {
+                    Value::Null => builder.append_null(),
+                    Value::Integer(v) => builder.append_value(*v as f64),
+                    Value::Real(v) => builder.append_value(*v),
+                    value => return Err(mismatch(value)),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Binary => {
+            let mut builder = BinaryBuilder::new();
+            for value in values {
+                match value {
+                    Value::Null => builder.append_null(),
+                    Value::Integer(v) => builder.append_value(v.to_string()),
+                    Value::Real(v) => builder.append_value(v.to_string()),
+                    Value::Text(v) => builder.append_value(v),
+                    Value::Blob(v) => builder.append_value(v),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        _ => {
+            let mut builder = StringBuilder::new();
+            for value in values {
+                match value {
+                    Value::Null => builder.append_null(),
+                    Value::Integer(v) => builder.append_value(v.to_string()),
+                    Value::Real(v) => builder.append_value(v.to_string()),
+                    Value::Text(v) => builder.append_value(v),
+                    value => return Err(mismatch(value)),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+    })
+}
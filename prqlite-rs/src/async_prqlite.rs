@@ -0,0 +1,138 @@
+use crate::{
+    InterruptHandle, OwnedRows, Prqlite, PrqliteBuilder, PrqliteError, Result, Transaction,
+};
+use std::{
+    sync::mpsc::{channel, Sender},
+    thread,
+};
+use tokio::sync::oneshot;
+
+type Job = Box<dyn FnOnce(&Prqlite) + Send>;
+
+/// Handle to a [`Prqlite`] owned by a dedicated worker thread, for async runtimes.
+///
+/// Every call is sent to the worker and awaited without blocking the runtime. The handle
+/// is cheap to clone; the worker stops once every clone has been dropped.
+#[derive(Debug, Clone)]
+pub struct AsyncPrqlite {
+    jobs: Sender<Job>,
+}
+
+impl AsyncPrqlite {
+    /// Move an opened handle onto a new worker thread.
+    pub fn new(prqlite: Prqlite) -> Self {
+        Self::spawn(move || Some(prqlite)).expect("failed to spawn the prqlite worker thread")
+    }
+    /// Open the database on the worker thread.
+    pub async fn open(path: &str) -> Result<Self> {
+        let mut builder = Prqlite::builder();
+        builder.path(path);
+        Self::open_with(builder).await
+    }
+    /// Open the database described by `builder` on the worker thread.
+    pub async fn open_with(builder: PrqliteBuilder) -> Result<Self> {
+        let (sender, receiver) = oneshot::channel();
+        let worker = Self::spawn(move || match builder.open() {
+            Ok(prqlite) => {
+                let _ = sender.send(Ok(()));
+                Some(prqlite)
+            }
+            Err(err) => {
+                let _ = sender.send(Err(err));
+                None
+            }
+        })
+        .map_err(|err| PrqliteError::Worker(err.to_string()))?;
+        receiver.await.map_err(|_| worker_stopped())??;
+        Ok(worker)
+    }
+    /// Start the worker thread, running jobs on the connection `open` returns.
+    fn spawn<F>(open: F) -> std::io::Result<Self>
+    where
+        F: FnOnce() -> Option<Prqlite> + Send + 'static,
+    {
+        let (jobs, receiver) = channel::<Job>();
+        thread::Builder::new()
+            .name("prqlite-worker".to_owned())
+            .spawn(move || {
+                if let Some(prqlite) = open() {
+                    for job in receiver {
+                        job(&prqlite);
+                    }
+                }
+            })?;
+        Ok(Self { jobs })
+    }
+
+    /// Run `f` with the connection on the worker thread and await its result.
+    pub async fn call<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Prqlite) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        self.jobs
+            .send(Box::new(move |prqlite| {
+                let _ = sender.send(f(prqlite));
+            }))
+            .map_err(|_| worker_stopped())?;
+        receiver.await.map_err(|_| worker_stopped())?
+    }
+
+    /// Handle interrupting the query running on the worker, see
+    /// [`Prqlite::interrupt_handle`].
+    pub async fn interrupt_handle(&self) -> Result<InterruptHandle> {
+        self.call(|prqlite| Ok(prqlite.interrupt_handle())).await
+    }
+
+    /// Run a PRQL query and load its whole result.
+    pub async fn query(&self, prql: &str) -> Result<OwnedRows> {
+        let prql = prql.to_owned();
+        self.call(move |prqlite| prqlite.query(&prql)).await
+    }
+    /// Run a PRQL query to completion without loading its rows, returning how many it
+    /// produced.
+    pub async fn execute(&self, prql: &str) -> Result<usize> {
+        let prql = prql.to_owned();
+        self.call(move |prqlite| {
+            let mut rows = 0;
+            for row in prqlite.stream(&prql)?.rows() {
+                row?;
+                rows += 1;
+            }
+            Ok(rows)
+        })
+        .await
+    }
+    /// Execute a SQL statement, returning the number of changed rows.
+    pub async fn execute_with_sql(&self, sql: &str) -> Result<usize> {
+        let sql = sql.to_owned();
+        self.call(move |prqlite| Ok(prqlite.execute_with_sql(&sql)?.execute([])?))
+            .await
+    }
+    /// Execute several `;`-separated SQL statements.
+    pub async fn execute_batch_with_sql(&self, sql: &str) -> Result<()> {
+        let sql = sql.to_owned();
+        self.call(move |prqlite| prqlite.execute_batch_with_sql(&sql))
+            .await
+    }
+    /// Run `f` inside a transaction, committed when it returns `Ok` and rolled back when
+    /// it returns an error.
+    pub async fn transaction<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Transaction) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.call(move |prqlite| {
+            let tx = prqlite.transaction()?;
+            let out = f(&tx)?;
+            tx.commit()?;
+            Ok(out)
+        })
+        .await
+    }
+}
+
+fn worker_stopped() -> PrqliteError {
+    PrqliteError::Worker("the worker thread owning the connection has stopped".to_owned())
+}
@@ -0,0 +1,68 @@
+use crate::{Prqlite, PrqliteError, Result};
+use rusqlite::{
+    backup::{Backup, Progress, StepResult},
+    Connection, OpenFlags,
+};
+use std::{ffi::c_int, path::Path, thread::sleep, time::Duration};
+
+/// Pages copied by each step of a backup.
+const PAGES_PER_STEP: c_int = 256;
+/// Pause between steps, during which other connections can write to the source.
+const PAUSE_BETWEEN_STEPS: Duration = Duration::from_millis(10);
+
+impl Prqlite {
+    /// Copy the `main` database into the file at `path` with SQLite's online backup API,
+    /// replacing its content. The copy is made a few pages at a time, so other
+    /// connections can keep writing while it runs. `progress` is called after every step.
+    pub fn backup_to<P: AsRef<Path>>(&self, path: P, progress: impl FnMut(Progress)) -> Result<()> {
+        let path = path.as_ref();
+        let mut dst = Connection::open(path).map_err(|source| PrqliteError::Open {
+            path: path.display().to_string(),
+            source,
+        })?;
+        copy_database(&self.conn, &mut dst, progress)
+    }
+
+    /// Replace the `main` database with the content of the database file at `path`.
+    ///
+    /// SQLite forbids using the destination of a backup while it runs, hence the
+    /// exclusive borrow: no statement of this handle can be alive.
+    pub fn restore_from<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        progress: impl FnMut(Progress),
+    ) -> Result<()> {
+        let path = path.as_ref();
+        let src = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(|source| PrqliteError::Open {
+            path: path.display().to_string(),
+            source,
+        })?;
+        let copied = copy_database(&src, &mut self.conn, progress);
+        // The schema changed under the prepared statements and the schema prelude.
+        self.clear_cache();
+        self.invalidate_prelude();
+        copied
+    }
+}
+
+/// Copy the `main` database of `src` into the one of `dst`, pausing between steps.
+fn copy_database(
+    src: &Connection,
+    dst: &mut Connection,
+    mut progress: impl FnMut(Progress),
+) -> Result<()> {
+    let backup = Backup::new(src, dst)?;
+    loop {
+        let step = backup.step(PAGES_PER_STEP)?;
+        progress(backup.progress());
+        match step {
+            StepResult::Done => return Ok(()),
+            // More, or Busy and Locked which are transient: step again after the pause.
+            _ => sleep(PAUSE_BETWEEN_STEPS),
+        }
+    }
+}
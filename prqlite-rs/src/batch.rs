@@ -0,0 +1,202 @@
+use prql_compiler::{ast::pl::StmtKind, prql_to_pl};
+use rusqlite::types::Value;
+
+/// A single PRQL query of a batch script, with the lines it spans (1-based, inclusive).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchQuery {
+    pub source: String,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// Outcome of one query of [`Prqlite::execute_batch`](crate::Prqlite::execute_batch).
+#[derive(Debug, Clone)]
+pub struct BatchResult {
+    pub query: BatchQuery,
+    pub sql: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// Split a PRQL script into separate queries.
+///
+/// Queries are separated by blank lines or by `;`, except when they occur inside strings,
+/// comments or brackets. Chunks that only declare things (`let`, `func`, `prql` header)
+/// are prepended to the query that follows them.
+pub fn split_queries(script: &str) -> Vec<BatchQuery> {
+    let mut chunks = vec![];
+    let mut scanner = Scanner::default();
+
+    let mut chars = script.chars().peekable();
+    while let Some(c) = chars.next() {
+        match scanner.string {
+            Some((quote, triple)) => {
+                scanner.push(c);
+                if c == '\\' {
+                    if let Some(escaped) = chars.next() {
+                        scanner.push(escaped);
+                    }
+                } else if c == quote {
+                    if !triple {
+                        scanner.string = None;
+                    } else if chars.peek() == Some(&quote) {
+                        scanner.push(chars.next().unwrap());
+                        if chars.peek() == Some(&quote) {
+                            scanner.push(chars.next().unwrap());
+                            scanner.string = None;
+                        }
+                    }
+                    if scanner.string.is_none() {
+                        scanner.significant();
+                    }
+                }
+            }
+            None if scanner.comment => {
+                if c == '\n' {
+                    scanner.comment = false;
+                    scanner.newline(&mut chunks);
+                } else {
+                    scanner.push(c);
+                }
+            }
+            None => match c {
+                '#' => {
+                    scanner.comment = true;
+                    scanner.line_blank = false;
+                    scanner.push(c);
+                }
+                '\'' | '"' => {
+                    scanner.significant();
+                    scanner.push(c);
+                    if chars.peek() == Some(&c) {
+                        scanner.push(chars.next().unwrap());
+                        // two quotes are an empty string, three open a multi-line one
+                        if chars.peek() == Some(&c) {
+                            scanner.push(chars.next().unwrap());
+                            scanner.string = Some((c, true));
+                        }
+                    } else {
+                        scanner.string = Some((c, false));
+                    }
+                }
+                '(' | '[' | '{' => {
+                    scanner.depth += 1;
+                    scanner.significant();
+                    scanner.push(c);
+                }
+                ')' | ']' | '}' => {
+                    scanner.depth = scanner.depth.saturating_sub(1);
+                    scanner.significant();
+                    scanner.push(c);
+                }
+                ';' if scanner.depth == 0 => scanner.finish(&mut chunks),
+                '\n' => scanner.newline(&mut chunks),
+                c if c.is_whitespace() => scanner.push(c),
+                c => {
+                    scanner.significant();
+                    scanner.push(c);
+                }
+            },
+        }
+    }
+    scanner.finish(&mut chunks);
+
+    merge_declarations(chunks)
+}
+
+/// Prepend chunks holding only declarations to the next chunk holding a main pipeline.
+fn merge_declarations(chunks: Vec<BatchQuery>) -> Vec<BatchQuery> {
+    let mut queries = vec![];
+    let mut pending: Option<BatchQuery> = None;
+
+    for chunk in chunks {
+        let chunk = match pending.take() {
+            Some(decls) => BatchQuery {
+                // keep the lines between both chunks so positions still match the script
+                source: format!(
+                    "{}{}{}",
+                    decls.source,
+                    "\n".repeat(chunk.start_line - decls.end_line),
+                    chunk.source
+                ),
+                start_line: decls.start_line,
+                end_line: chunk.end_line,
+            },
+            None => chunk,
+        };
+        let declarations_only = prql_to_pl(&chunk.source)
+            .map(|stmts| !stmts.iter().any(|s| matches!(s.kind, StmtKind::Main(_))))
+            .unwrap_or(false);
+
+        if declarations_only {
+            pending = Some(chunk);
+        } else {
+            queries.push(chunk);
+        }
+    }
+    queries.extend(pending);
+    queries
+}
+
+struct Scanner {
+    current: String,
+    line: usize,
+    start_line: Option<usize>,
+    end_line: usize,
+    line_blank: bool,
+    depth: usize,
+    comment: bool,
+    string: Option<(char, bool)>,
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self {
+            current: String::new(),
+            line: 1,
+            start_line: None,
+            end_line: 1,
+            line_blank: true,
+            depth: 0,
+            comment: false,
+            string: None,
+        }
+    }
+}
+
+impl Scanner {
+    fn push(&mut self, c: char) {
+        if c == '\n' {
+            self.line += 1;
+        }
+        self.current.push(c);
+    }
+    /// Mark the current position as part of a query rather than whitespace or comments.
+    fn significant(&mut self) {
+        self.line_blank = false;
+        if self.start_line.is_none() {
+            // drop the blank lines and comments that separate this query from the previous one
+            self.current.clear();
+            self.start_line = Some(self.line);
+        }
+        self.end_line = self.line;
+    }
+    fn newline(&mut self, chunks: &mut Vec<BatchQuery>) {
+        if self.line_blank && self.depth == 0 {
+            self.finish(chunks);
+        }
+        self.push('\n');
+        self.line_blank = true;
+    }
+    fn finish(&mut self, chunks: &mut Vec<BatchQuery>) {
+        let source = std::mem::take(&mut self.current);
+        if let Some(start_line) = self.start_line.take() {
+            chunks.push(BatchQuery {
+                source: source.trim_end().to_owned(),
+                start_line,
+                end_line: self.end_line,
+            });
+        }
+        self.depth = 0;
+    }
+}
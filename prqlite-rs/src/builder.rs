@@ -0,0 +1,197 @@
+use crate::{
+    cache::{options_key, CompileCache},
+    Prqlite, PrqliteError, Result,
+};
+use prql_compiler::{sql::Dialect, Options, Target};
+use rusqlite::{Connection, OpenFlags};
+use std::{
+    cell::{Cell, RefCell},
+    time::Duration,
+};
+
+const DEFAULT_PATH: &str = ":memory:";
+const DEFAULT_CACHE_CAPACITY: usize = 128;
+
+/// Compile options used when none are given: compact SQL for SQLite, without the
+/// compiler signature comment.
+pub fn default_compile_options() -> Options {
+    Options::default()
+        .no_format()
+        .no_signature()
+        .with_target(Target::Sql(Some(Dialect::SQLite)))
+}
+
+/// Builder for [`Prqlite`], created by [`Prqlite::builder`].
+#[derive(Debug, Clone)]
+pub struct PrqliteBuilder {
+    path: Option<String>,
+    compile_options: Option<Options>,
+    cache_capacity: Option<usize>,
+    declare_schema: bool,
+    read_only: bool,
+    create: bool,
+    uri: bool,
+    shared_cache: bool,
+    busy_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+}
+
+impl PrqliteBuilder {
+    pub(crate) fn new() -> Self {
+        Self {
+            path: None,
+            compile_options: None,
+            cache_capacity: None,
+            declare_schema: false,
+            read_only: false,
+            create: true,
+            uri: true,
+            shared_cache: false,
+            busy_timeout: None,
+            timeout: None,
+        }
+    }
+    /// Database file to open, defaults to a transient in-memory database.
+    pub fn path(&mut self, path: &str) -> &mut Self {
+        self.path = Some(path.to_owned());
+        self
+    }
+    /// Replace all compile options at once.
+    pub fn compile_options(&mut self, options: Options) -> &mut Self {
+        self.compile_options = Some(options);
+        self
+    }
+    /// Pretty-print the SQL returned by [`Prqlite::compile`].
+    pub fn format(&mut self, format: bool) -> &mut Self {
+        let mut options = self.options();
+        options.format = format;
+        self.compile_options = Some(options);
+        self
+    }
+    /// Append the PRQL compiler signature comment to the generated SQL.
+    pub fn signature_comment(&mut self, signature_comment: bool) -> &mut Self {
+        self.compile_options = Some(self.options().with_signature_comment(signature_comment));
+        self
+    }
+    /// Use ANSI colors in compile error messages.
+    pub fn color(&mut self, color: bool) -> &mut Self {
+        self.compile_options = Some(self.options().with_color(color));
+        self
+    }
+    /// Target used by [`Prqlite::compile`]. Queries executed against the database are
+    /// always compiled for SQLite, whatever the target is.
+    pub fn target(&mut self, target: Target) -> &mut Self {
+        self.compile_options = Some(self.options().with_target(target));
+        self
+    }
+    /// Number of compiled queries, and of prepared statements, kept around for reuse.
+    /// `0` disables caching.
+    pub fn cache_capacity(&mut self, capacity: usize) -> &mut Self {
+        self.cache_capacity = Some(capacity);
+        self
+    }
+    /// Declare the tables of the database, with their columns, to the PRQL compiler so
+    /// unknown columns are reported as compile errors. See [`schema_prelude`](crate::schema_prelude).
+    pub fn declare_schema(&mut self, declare_schema: bool) -> &mut Self {
+        self.declare_schema = declare_schema;
+        self
+    }
+    /// Open the database read-only, failing if it doesn't exist.
+    pub fn read_only(&mut self, read_only: bool) -> &mut Self {
+        self.read_only = read_only;
+        self
+    }
+    /// Create the database file when it doesn't exist, which is the default. Without it,
+    /// opening a missing file fails instead of creating an empty database.
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+    /// Interpret paths starting with `file:` as URI filenames, such as
+    /// `file:data.db?mode=ro`, which is the default.
+    pub fn uri(&mut self, uri: bool) -> &mut Self {
+        self.uri = uri;
+        self
+    }
+    /// Open the database in shared-cache mode.
+    pub fn shared_cache(&mut self, shared_cache: bool) -> &mut Self {
+        self.shared_cache = shared_cache;
+        self
+    }
+    /// Open the in-memory database `name`, shared by every connection of the process
+    /// opening the same name, instead of a file.
+    pub fn shared_memory(&mut self, name: &str) -> &mut Self {
+        self.path = Some(format!("file:{name}?mode=memory&cache=shared"));
+        self.uri = true;
+        self
+    }
+    /// How long to wait for a lock held by another connection before failing with
+    /// `SQLITE_BUSY`. By default it fails right away.
+    pub fn busy_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.busy_timeout = Some(timeout);
+        self
+    }
+    /// Interrupt queries running longer than `timeout`. See [`Prqlite::set_timeout`].
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+    pub fn open(&self) -> Result<Prqlite> {
+        let path = self.path.as_deref().unwrap_or(DEFAULT_PATH);
+        let open_error = |source| PrqliteError::Open {
+            path: path.to_owned(),
+            source,
+        };
+        let conn = Connection::open_with_flags(path, self.open_flags()).map_err(open_error)?;
+        if let Some(timeout) = self.busy_timeout {
+            conn.busy_timeout(timeout).map_err(open_error)?;
+        }
+        let cache_capacity = self.cache_capacity.unwrap_or(DEFAULT_CACHE_CAPACITY);
+        conn.set_prepared_statement_cache_capacity(cache_capacity);
+
+        let compile_options = self.options();
+        let sqlite_options = compile_options
+            .clone()
+            .with_target(Target::Sql(Some(Dialect::SQLite)));
+        let prqlite = Prqlite {
+            conn,
+            options_key: options_key(&sqlite_options),
+            sqlite_options,
+            compile_options,
+            cache: RefCell::new(CompileCache::new(cache_capacity)),
+            declare_schema: self.declare_schema,
+            prelude: RefCell::new(None),
+            functions: RefCell::new(vec![]),
+            timeout: Cell::new(None),
+            deadline: Default::default(),
+        };
+        if self.timeout.is_some() {
+            prqlite.set_timeout(self.timeout);
+        }
+        Ok(prqlite)
+    }
+
+    fn open_flags(&self) -> OpenFlags {
+        let mut flags = OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        match self.read_only {
+            true => flags |= OpenFlags::SQLITE_OPEN_READ_ONLY,
+            false => flags |= OpenFlags::SQLITE_OPEN_READ_WRITE,
+        }
+        if self.create && !self.read_only {
+            flags |= OpenFlags::SQLITE_OPEN_CREATE;
+        }
+        if self.uri {
+            flags |= OpenFlags::SQLITE_OPEN_URI;
+        }
+        if self.shared_cache {
+            flags |= OpenFlags::SQLITE_OPEN_SHARED_CACHE;
+        }
+        flags
+    }
+
+    fn options(&self) -> Options {
+        self.compile_options
+            .clone()
+            .unwrap_or_else(default_compile_options)
+    }
+}
@@ -0,0 +1,84 @@
+use hashlink::LruCache;
+use prql_compiler::Options;
+use std::sync::Arc;
+
+/// Counters of the PRQL-to-SQL compile cache, returned by
+/// [`Prqlite::cache_stats`](crate::Prqlite::cache_stats).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub len: usize,
+    pub capacity: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    prql: String,
+    options: Arc<str>,
+}
+
+/// Key of compile options in the cache, computed once per handle rather than on every
+/// lookup.
+pub(crate) fn options_key(options: &Options) -> Arc<str> {
+    format!("{options:?}").into()
+}
+
+/// LRU cache of compiled SQL, keyed on the PRQL source and the compile options.
+pub(crate) struct CompileCache {
+    entries: LruCache<CacheKey, String>,
+    hits: u64,
+    misses: u64,
+}
+
+impl CompileCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: LruCache::new(capacity),
+            hits: 0,
+            misses: 0,
+        }
+    }
+    pub fn get_or_compile<E>(
+        &mut self,
+        prql: &str,
+        options: &Arc<str>,
+        compile: impl FnOnce() -> Result<String, E>,
+    ) -> Result<String, E> {
+        if self.entries.capacity() == 0 {
+            self.misses += 1;
+            return compile();
+        }
+        let key = CacheKey {
+            prql: prql.to_owned(),
+            options: Arc::clone(options),
+        };
+        if let Some(sql) = self.entries.get(&key) {
+            self.hits += 1;
+            return Ok(sql.clone());
+        }
+        self.misses += 1;
+        let sql = compile()?;
+        self.entries.insert(key, sql.clone());
+        Ok(sql)
+    }
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            len: self.entries.len(),
+            capacity: self.entries.capacity(),
+        }
+    }
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl std::fmt::Debug for CompileCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompileCache")
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
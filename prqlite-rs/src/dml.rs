@@ -0,0 +1,68 @@
+use crate::{schema::quote_identifier, Prqlite, PrqliteError, Result};
+
+impl Prqlite {
+    /// Insert the rows of a PRQL relation into `table`, matching its columns by name,
+    /// and return the number of inserted rows.
+    ///
+    /// ```
+    /// # fn main() -> prqlite_rs::Result<()> {
+    /// # let conn = prqlite_rs::Prqlite::open(":memory:")?;
+    /// # conn.execute_batch_with_sql("CREATE TABLE orders (id integer, total real);
+    /// #     CREATE TABLE big_orders (id integer, total real)")?;
+    /// conn.insert_into("big_orders", "from orders | filter total > 100")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn insert_into(&self, table: &str, prql: &str) -> Result<usize> {
+        self.insert("INSERT", table, prql)
+    }
+    /// Like [`Prqlite::insert_into`], replacing the rows conflicting with a unique
+    /// constraint instead of failing.
+    pub fn replace_into(&self, table: &str, prql: &str) -> Result<usize> {
+        self.insert("INSERT OR REPLACE", table, prql)
+    }
+    /// Create `table` from the columns and rows of a PRQL relation.
+    pub fn create_table_as(&self, table: &str, prql: &str) -> Result<usize> {
+        let sql = self.compile_for_sqlite(prql)?;
+        self.run_wrapped(
+            prql,
+            &sql,
+            format!("CREATE TABLE {} AS {sql}", quote_identifier(table)),
+        )?;
+        let count = format!("SELECT COUNT(*) FROM {}", quote_identifier(table));
+        Ok(self.conn.query_row(&count, [], |row| row.get(0))?)
+    }
+    /// Create a view `name` over a PRQL relation.
+    pub fn create_view(&self, name: &str, prql: &str) -> Result<()> {
+        let sql = self.compile_for_sqlite(prql)?;
+        self.run_wrapped(
+            prql,
+            &sql,
+            format!("CREATE VIEW {} AS {sql}", quote_identifier(name)),
+        )?;
+        Ok(())
+    }
+
+    fn insert(&self, verb: &str, table: &str, prql: &str) -> Result<usize> {
+        let sql = self.compile_for_sqlite(prql)?;
+        let columns = self
+            .prepare_compiled(prql, &sql)?
+            .column_names()
+            .iter()
+            .map(|name| quote_identifier(name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.run_wrapped(
+            prql,
+            &sql,
+            format!("{verb} INTO {} ({columns}) {sql}", quote_identifier(table)),
+        )
+    }
+
+    /// Run `statement`, built around the SQL compiled from `prql`.
+    fn run_wrapped(&self, prql: &str, sql: &str, statement: String) -> Result<usize> {
+        self.conn
+            .execute(&statement, [])
+            .map_err(|err| PrqliteError::from(err).with_origin(prql, sql))
+    }
+}
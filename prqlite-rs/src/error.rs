@@ -0,0 +1,174 @@
+use crate::QueryOrigin;
+use prql_compiler::ErrorMessages;
+use thiserror::Error;
+
+pub type Result<T, E = PrqliteError> = std::result::Result<T, E>;
+
+/// Errors returned by every operation on [`Prqlite`](crate::Prqlite).
+#[derive(Debug, Error)]
+pub enum PrqliteError {
+    /// The PRQL query could not be compiled into SQL. Each message carries its reason,
+    /// hint, span and line/column location within the PRQL source.
+    #[error("{0}")]
+    Compile(ErrorMessages),
+
+    /// SQLite rejected the generated SQL or failed while running it.
+    #[error("{source}{}", origin_suffix(.origin))]
+    Sqlite {
+        /// SQLite extended result code, when the failure came from SQLite itself.
+        extended_code: Option<i32>,
+        #[source]
+        source: rusqlite::Error,
+        /// The generated SQL and the PRQL it came from, when SQLite failed on a PRQL query.
+        origin: Option<Box<QueryOrigin>>,
+    },
+
+    /// The database file could not be opened.
+    #[error("cannot open database '{path}': {source}")]
+    Open {
+        path: String,
+        #[source]
+        source: rusqlite::Error,
+    },
+
+    /// A query of a batch script failed. `index` is 0-based, lines are 1-based.
+    #[error("query #{} (lines {start_line}-{end_line}) failed: {source}", .index + 1)]
+    Batch {
+        index: usize,
+        start_line: usize,
+        end_line: usize,
+        #[source]
+        source: Box<PrqliteError>,
+    },
+
+    /// The values passed for the query parameters don't match its placeholders.
+    #[error("{0}")]
+    Parameter(String),
+
+    /// The worker thread of an [`AsyncPrqlite`](crate::AsyncPrqlite) is gone.
+    #[error("{0}")]
+    Worker(String),
+
+    /// The query was cancelled through an [`InterruptHandle`](crate::InterruptHandle),
+    /// or ran longer than the timeout set with [`Prqlite::set_timeout`](crate::Prqlite::set_timeout).
+    #[error("query interrupted")]
+    Interrupted { source: rusqlite::Error },
+
+    /// A [`PrqlitePool`](crate::PrqlitePool) can't be set up as configured.
+    #[error("{0}")]
+    Pool(String),
+
+    /// A file can't be imported with [`Prqlite::import_csv`](crate::Prqlite::import_csv).
+    #[error("{0}")]
+    Import(String),
+
+    /// A query result can't be exported with [`Prqlite::export`](crate::Prqlite::export).
+    #[error("{0}")]
+    Export(String),
+
+    /// Writing exported rows failed.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// A query result can't be converted into Arrow record batches.
+    #[cfg(feature = "arrow")]
+    #[error(transparent)]
+    Arrow(#[from] arrow_schema::ArrowError),
+
+    /// A column requested by a [`FromRow`](crate::FromRow) mapping is not in the result.
+    #[error("column '{name}' is missing from the query result, available columns: [{}]", .available.join(", "))]
+    MissingColumn {
+        name: String,
+        available: Vec<String>,
+    },
+
+    /// A column exists but its value cannot be converted into the requested type.
+    #[error("column '{name}' cannot be converted into `{target}`: {source}")]
+    ColumnType {
+        name: String,
+        target: &'static str,
+        #[source]
+        source: rusqlite::Error,
+    },
+}
+
+impl PrqliteError {
+    /// SQLite extended result code of the error, if it has one.
+    pub fn extended_code(&self) -> Option<i32> {
+        match self {
+            PrqliteError::Sqlite { extended_code, .. } => *extended_code,
+            PrqliteError::Open { source, .. } => extended_code(source),
+            PrqliteError::Batch { source, .. } => source.extended_code(),
+            PrqliteError::Interrupted { source } => extended_code(source),
+            _ => None,
+        }
+    }
+    /// Whether the query was interrupted rather than failing, including inside a batch.
+    pub fn is_interrupted(&self) -> bool {
+        match self {
+            PrqliteError::Interrupted { .. } => true,
+            PrqliteError::Batch { source, .. } => source.is_interrupted(),
+            _ => false,
+        }
+    }
+}
+
+impl PrqliteError {
+    /// Attach the PRQL query and the SQL compiled from it to a SQLite error.
+    pub(crate) fn with_origin(self, prql: &str, sql: &str) -> Self {
+        match self {
+            PrqliteError::Sqlite {
+                extended_code,
+                source,
+                origin: None,
+            } => PrqliteError::Sqlite {
+                origin: Some(Box::new(QueryOrigin::locate(&source, prql, sql))),
+                extended_code,
+                source,
+            },
+            err => err,
+        }
+    }
+    /// Where the error comes from in the PRQL query, for SQLite errors on compiled PRQL.
+    pub fn origin(&self) -> Option<&QueryOrigin> {
+        match self {
+            PrqliteError::Sqlite { origin, .. } => origin.as_deref(),
+            PrqliteError::Batch { source, .. } => source.origin(),
+            _ => None,
+        }
+    }
+}
+
+impl From<ErrorMessages> for PrqliteError {
+    fn from(err: ErrorMessages) -> Self {
+        PrqliteError::Compile(err)
+    }
+}
+
+impl From<rusqlite::Error> for PrqliteError {
+    fn from(err: rusqlite::Error) -> Self {
+        if err.sqlite_error_code() == Some(rusqlite::ErrorCode::OperationInterrupted) {
+            return PrqliteError::Interrupted { source: err };
+        }
+        PrqliteError::Sqlite {
+            extended_code: extended_code(&err),
+            source: err,
+            origin: None,
+        }
+    }
+}
+
+fn extended_code(err: &rusqlite::Error) -> Option<i32> {
+    match err {
+        rusqlite::Error::SqliteFailure(err, _) => Some(err.extended_code),
+        rusqlite::Error::SqlInputError { error, .. } => Some(error.extended_code),
+        _ => None,
+    }
+}
+
+fn origin_suffix(origin: &Option<Box<QueryOrigin>>) -> String {
+    match origin.as_ref().and_then(|o| o.prql_location.as_ref()) {
+        Some(location) => format!(" (PRQL {location})"),
+        None => String::new(),
+    }
+}
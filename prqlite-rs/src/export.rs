@@ -0,0 +1,294 @@
+use crate::{Prqlite, PrqliteError, QueryResult, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rusqlite::types::Value;
+use serde_json::{Map, Value as Json};
+use std::{
+    io::{self, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+/// Text formats query results can be exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    /// A single JSON array of objects keyed by column name.
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+    /// A GitHub flavored Markdown table.
+    Markdown,
+    /// An HTML `<table>`.
+    Html,
+}
+
+impl ExportFormat {
+    /// Guess the format from the extension of `path`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?;
+        extension.parse().ok()
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = PrqliteError;
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            "ndjson" | "jsonl" => Ok(ExportFormat::Ndjson),
+            "md" | "markdown" => Ok(ExportFormat::Markdown),
+            "html" | "htm" => Ok(ExportFormat::Html),
+            _ => Err(PrqliteError::Export(format!(
+                "unknown export format '{s}', use csv, json, ndjson, markdown or html"
+            ))),
+        }
+    }
+}
+
+/// How blobs are written, as text, by [`Prqlite::export`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BlobEncoding {
+    #[default]
+    Base64,
+    Hex,
+}
+
+impl FromStr for BlobEncoding {
+    type Err = PrqliteError;
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "base64" => Ok(BlobEncoding::Base64),
+            "hex" => Ok(BlobEncoding::Hex),
+            _ => Err(PrqliteError::Export(format!(
+                "unknown blob encoding '{s}', use base64 or hex"
+            ))),
+        }
+    }
+}
+
+impl BlobEncoding {
+    /// Encode `bytes` as text.
+    pub fn encode(&self, bytes: &[u8]) -> String {
+        match self {
+            BlobEncoding::Base64 => STANDARD.encode(bytes),
+            BlobEncoding::Hex => bytes.iter().map(|b| format!("{b:02x}")).collect(),
+        }
+    }
+}
+
+/// Settings of [`Prqlite::export`].
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    format: ExportFormat,
+    blobs: BlobEncoding,
+}
+
+impl ExportOptions {
+    pub fn new(format: ExportFormat) -> Self {
+        Self {
+            format,
+            blobs: BlobEncoding::default(),
+        }
+    }
+    pub fn blob_encoding(&mut self, blobs: BlobEncoding) -> &mut Self {
+        self.blobs = blobs;
+        self
+    }
+    pub fn format(&self) -> ExportFormat {
+        self.format
+    }
+}
+
+impl Prqlite {
+    /// Run a PRQL query and write its rows to `out` as they are read, returning the
+    /// number of rows written.
+    ///
+    /// `NULL` is written as an empty field in CSV, Markdown and HTML and as `null` in
+    /// JSON. Blobs are written as base64 or hex text.
+    ///
+    /// ```
+    /// # fn main() -> prqlite_rs::Result<()> {
+    /// # let conn = prqlite_rs::Prqlite::open(":memory:")?;
+    /// # conn.execute_batch_with_sql("CREATE TABLE persons (id integer, name text)")?;
+    /// use prqlite_rs::{ExportFormat, ExportOptions};
+    ///
+    /// let mut csv = vec![];
+    /// conn.export("from persons", &ExportOptions::new(ExportFormat::Csv), &mut csv)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn export<W: Write>(&self, prql: &str, options: &ExportOptions, out: W) -> Result<usize> {
+        self.stream(prql)?.export(options, out)
+    }
+}
+
+impl QueryResult<'_> {
+    /// Write the rows of the result to `out`, see [`Prqlite::export`].
+    pub fn export<W: Write>(&mut self, options: &ExportOptions, out: W) -> Result<usize> {
+        let columns = self
+            .column_names()
+            .into_iter()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        let out = match options.format {
+            ExportFormat::Csv => Output::Csv(Box::new(csv::Writer::from_writer(out))),
+            _ => Output::Text(BufWriter::new(out)),
+        };
+        let mut exporter = Exporter {
+            out,
+            options,
+            columns: &columns,
+            rows: 0,
+        };
+        exporter.header()?;
+        for row in self.rows() {
+            exporter.row(&row?)?;
+        }
+        exporter.footer()?;
+        Ok(exporter.rows)
+    }
+}
+
+/// CSV goes through its own writer, which takes care of the quoting; the other
+/// formats are written as text.
+enum Output<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    Text(BufWriter<W>),
+}
+
+struct Exporter<'a, W: Write> {
+    out: Output<W>,
+    options: &'a ExportOptions,
+    columns: &'a [String],
+    rows: usize,
+}
+
+impl<W: Write> Exporter<'_, W> {
+    fn header(&mut self) -> Result<()> {
+        let columns = self.columns;
+        let out = match &mut self.out {
+            Output::Csv(csv) => return Ok(csv.write_record(columns).map_err(io::Error::from)?),
+            Output::Text(out) => out,
+        };
+        match self.options.format {
+            ExportFormat::Json => write!(out, "[")?,
+            ExportFormat::Csv | ExportFormat::Ndjson => {}
+            ExportFormat::Markdown => {
+                let names = columns.iter().map(|c| markdown_cell(c)).collect::<Vec<_>>();
+                writeln!(out, "| {} |", names.join(" | "))?;
+                writeln!(out, "|{}", " --- |".repeat(columns.len()))?;
+            }
+            ExportFormat::Html => {
+                let names = columns
+                    .iter()
+                    .map(|c| format!("<th>{}</th>", html_escape(c)))
+                    .collect::<String>();
+                writeln!(out, "<table>\n<thead>\n<tr>{names}</tr>\n</thead>\n<tbody>")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn row(&mut self, values: &[Value]) -> Result<()> {
+        let blobs = self.options.blobs;
+        let text = |value: &Value| plain_text(value, blobs);
+        let first = self.rows == 0;
+        self.rows += 1;
+        let out = match &mut self.out {
+            Output::Csv(csv) => {
+                let fields = values.iter().map(text);
+                return Ok(csv.write_record(fields).map_err(io::Error::from)?);
+            }
+            Output::Text(out) => out,
+        };
+        match self.options.format {
+            ExportFormat::Json => {
+                writeln!(out, "{}", if first { "" } else { "," })?;
+                serde_json::to_writer(&mut *out, &json_object(self.columns, values, blobs))
+                    .map_err(io::Error::from)?;
+            }
+            ExportFormat::Ndjson => {
+                serde_json::to_writer(&mut *out, &json_object(self.columns, values, blobs))
+                    .map_err(io::Error::from)?;
+                writeln!(out)?;
+            }
+            ExportFormat::Csv => unreachable!("CSV is written by its own writer"),
+            ExportFormat::Markdown => {
+                let cells = values.iter().map(|v| markdown_cell(&text(v)));
+                writeln!(out, "| {} |", cells.collect::<Vec<_>>().join(" | "))?;
+            }
+            ExportFormat::Html => {
+                let cells = values
+                    .iter()
+                    .map(|v| format!("<td>{}</td>", html_escape(&text(v))))
+                    .collect::<String>();
+                writeln!(out, "<tr>{cells}</tr>")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn footer(&mut self) -> Result<()> {
+        let out = match &mut self.out {
+            Output::Csv(csv) => return Ok(csv.flush()?),
+            Output::Text(out) => out,
+        };
+        match self.options.format {
+            ExportFormat::Json if self.rows == 0 => writeln!(out, "]")?,
+            ExportFormat::Json => writeln!(out, "\n]")?,
+            ExportFormat::Html => writeln!(out, "</tbody>\n</table>")?,
+            ExportFormat::Csv | ExportFormat::Ndjson | ExportFormat::Markdown => {}
+        }
+        Ok(out.flush()?)
+    }
+}
+
+/// A row as a JSON object keyed by column name, in the order of the columns.
+fn json_object(columns: &[String], values: &[Value], blobs: BlobEncoding) -> Json {
+    let object = columns.iter().zip(values).map(|(column, value)| {
+        let value = match value {
+            Value::Null => Json::Null,
+            Value::Integer(v) => Json::from(*v),
+            // Infinities and NaN have no JSON representation and become null.
+            Value::Real(v) => Json::from(*v),
+            value => Json::String(plain_text(value, blobs)),
+        };
+        (column.clone(), value)
+    });
+    Json::Object(object.collect::<Map<_, _>>())
+}
+
+/// A value as text, `NULL` being empty and blobs encoded.
+fn plain_text(value: &Value, blobs: BlobEncoding) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Integer(v) => v.to_string(),
+        Value::Real(v) => v.to_string(),
+        Value::Text(v) => v.clone(),
+        Value::Blob(v) => blobs.encode(v),
+    }
+}
+
+/// Escape pipes and line breaks, which would end the cell or the row.
+fn markdown_cell(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace(['\n', '\r'], "<br>")
+}
+
+fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,231 @@
+use crate::{
+    import::{column_names, open_file, qualified_name},
+    schema::quote_identifier,
+    CsvOptions, ImportReport, Prqlite, PrqliteError, RejectedRow, Result, Transaction,
+};
+use rusqlite::types::Value;
+use serde_json::{Map, Value as Json};
+use std::{io::Read, path::Path};
+
+/// Records read to infer the column types of a JSON file.
+const JSON_SAMPLE_SIZE: usize = 1000;
+
+/// Formats of the files [`Prqlite::register_file_table`] reads.
+#[derive(Debug, Clone)]
+pub enum FileTableFormat {
+    Csv(CsvOptions),
+    /// A JSON array of objects.
+    Json,
+    /// One JSON object per line, also known as JSON lines.
+    Ndjson,
+}
+
+impl FileTableFormat {
+    /// Guess the format from the extension of `path`: `.csv`, `.tsv`, `.tab`, `.json`,
+    /// `.ndjson` or `.jsonl`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let path = path.as_ref();
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "csv" | "tsv" | "tab" => Some(FileTableFormat::Csv(CsvOptions::for_path(path))),
+            "json" => Some(FileTableFormat::Json),
+            "ndjson" | "jsonl" => Some(FileTableFormat::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+/// A JSON record along with its 1-based line, or why it can't be imported.
+type JsonRecord = (u64, std::result::Result<Map<String, Json>, String>);
+
+impl Prqlite {
+    /// Load a CSV or JSON file into the temporary table `name`, so that it can be queried
+    /// with `from name` until the connection is closed.
+    ///
+    /// Column types are inferred like [`Prqlite::import_csv`] does. The columns of a JSON
+    /// file are the keys of its objects, nested objects and arrays being kept as JSON
+    /// text. A table registered under the same name is replaced, which also re-reads a
+    /// file that changed; when the file can't be loaded, the previous table is kept. The
+    /// table hides a `main` table of the same name.
+    pub fn register_file_table<P: AsRef<Path>>(
+        &self,
+        name: &str,
+        path: P,
+        format: &FileTableFormat,
+    ) -> Result<ImportReport> {
+        let mut file = open_file(path.as_ref())?;
+        let tx = Transaction::begin_savepoint(self, "prqlite_file_table")?;
+        self.conn.execute_batch(&format!(
+            "DROP TABLE IF EXISTS {}",
+            qualified_name("temp", name)
+        ))?;
+        let report = match format {
+            FileTableFormat::Csv(options) => self.import_csv_into(file, "temp", name, options)?,
+            FileTableFormat::Json | FileTableFormat::Ndjson => {
+                let mut text = String::new();
+                file.read_to_string(&mut text)
+                    .map_err(|err| PrqliteError::Import(err.to_string()))?;
+                let records = match format {
+                    FileTableFormat::Json => json_array_elements(&text)?,
+                    _ => text
+                        .lines()
+                        .enumerate()
+                        .filter(|(_, line)| !line.trim().is_empty())
+                        .map(|(idx, line)| {
+                            let object = serde_json::from_str(line)
+                                .map_err(|err| err.to_string())
+                                .and_then(json_object);
+                            (idx as u64 + 1, object)
+                        })
+                        .collect(),
+                };
+                self.import_json_into(records, name)?
+            }
+        };
+        tx.commit()?;
+        Ok(report)
+    }
+
+    /// Create the temporary table `table` from JSON objects and insert them.
+    fn import_json_into(&self, records: Vec<JsonRecord>, table: &str) -> Result<ImportReport> {
+        // Keys in order of first appearance, with the JSON types seen for them.
+        let mut keys: Vec<(String, Option<&'static str>)> = vec![];
+        for (_, record) in records.iter().take(JSON_SAMPLE_SIZE) {
+            for (key, value) in record.iter().flatten() {
+                let ty = sql_type(value);
+                match keys.iter_mut().find(|(k, _)| k == key) {
+                    Some((_, seen)) => *seen = merge_types(*seen, ty),
+                    None => keys.push((key.clone(), ty)),
+                }
+            }
+        }
+        if keys.is_empty() {
+            return Err(PrqliteError::Import(format!(
+                "no columns to import into '{table}'"
+            )));
+        }
+        let names = column_names(keys.iter().map(|(key, _)| key.clone()).collect());
+        let columns = names
+            .iter()
+            .zip(&keys)
+            .map(|(name, (_, ty))| (name.clone(), ty.unwrap_or("TEXT").to_owned()))
+            .collect::<Vec<_>>();
+
+        let target = qualified_name("temp", table);
+        let definitions = columns
+            .iter()
+            .map(|(name, ty)| format!("{} {ty}", quote_identifier(name)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.conn
+            .execute_batch(&format!("CREATE TABLE {target} ({definitions})"))?;
+        let placeholders = vec!["?"; names.len()].join(", ");
+        let mut insert = self
+            .conn
+            .prepare(&format!("INSERT INTO {target} VALUES ({placeholders})"))?;
+
+        let mut inserted = 0;
+        let mut rejected = vec![];
+        for (line, record) in records {
+            let fields = match record {
+                Ok(fields) => fields,
+                Err(reason) => {
+                    rejected.push(RejectedRow { line, reason });
+                    continue;
+                }
+            };
+            let mut values = vec![Value::Null; keys.len()];
+            for (key, value) in fields {
+                if let Some(idx) = keys.iter().position(|(k, _)| *k == key) {
+                    values[idx] = sql_value(value);
+                }
+            }
+            match insert.execute(rusqlite::params_from_iter(values)) {
+                Ok(_) => inserted += 1,
+                Err(err) => rejected.push(RejectedRow {
+                    line,
+                    reason: err.to_string(),
+                }),
+            }
+        }
+
+        Ok(ImportReport {
+            table: table.to_owned(),
+            created: true,
+            columns,
+            inserted,
+            rejected,
+        })
+    }
+}
+
+/// Elements of a JSON array, numbered from 1 as if they were lines.
+fn json_array_elements(text: &str) -> Result<Vec<JsonRecord>> {
+    let json = serde_json::from_str(text)
+        .map_err(|err| PrqliteError::Import(format!("invalid JSON: {err}")))?;
+    let Json::Array(elements) = json else {
+        return Err(PrqliteError::Import(
+            "a JSON file should hold an array of objects".to_owned(),
+        ));
+    };
+    Ok(elements
+        .into_iter()
+        .enumerate()
+        .map(|(idx, element)| (idx as u64 + 1, json_object(element)))
+        .collect())
+}
+
+fn json_object(json: Json) -> std::result::Result<Map<String, Json>, String> {
+    match json {
+        Json::Object(object) => Ok(object),
+        json => Err(format!(
+            "expected a JSON object, found {}",
+            json_type(&json)
+        )),
+    }
+}
+
+fn json_type(json: &Json) -> &'static str {
+    match json {
+        Json::Null => "null",
+        Json::Bool(_) => "boolean",
+        Json::Number(_) => "number",
+        Json::String(_) => "string",
+        Json::Array(_) => "array",
+        Json::Object(_) => "object",
+    }
+}
+
+/// Column type of a JSON value, `None` for `null`.
+fn sql_type(json: &Json) -> Option<&'static str> {
+    match json {
+        Json::Null => None,
+        Json::Bool(_) => Some("INTEGER"),
+        Json::Number(n) if n.is_i64() => Some("INTEGER"),
+        Json::Number(_) => Some("REAL"),
+        _ => Some("TEXT"),
+    }
+}
+
+/// SQLite value of a JSON value, nested arrays and objects being kept as JSON text.
+fn sql_value(json: Json) -> Value {
+    match json {
+        Json::Null => Value::Null,
+        Json::Bool(b) => Value::Integer(b.into()),
+        Json::Number(n) => match n.as_i64() {
+            Some(n) => Value::Integer(n),
+            None => n.as_f64().map_or(Value::Null, Value::Real),
+        },
+        Json::String(s) => Value::Text(s),
+        json => Value::Text(json.to_string()),
+    }
+}
+
+fn merge_types(a: Option<&'static str>, b: Option<&'static str>) -> Option<&'static str> {
+    match (a, b) {
+        (None, ty) | (ty, None) => ty,
+        (Some(a), Some(b)) if a == b => Some(a),
+        (Some("INTEGER" | "REAL"), Some("INTEGER" | "REAL")) => Some("REAL"),
+        _ => Some("TEXT"),
+    }
+}
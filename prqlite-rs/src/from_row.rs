@@ -0,0 +1,62 @@
+use crate::{PrqliteError, Result};
+use rusqlite::{types::FromSql, Row};
+
+/// Build a value from a single result row, looking columns up by name so the mapping
+/// keeps working when a PRQL `select` reorders columns.
+///
+/// Usually implemented with [`impl_from_row!`](crate::impl_from_row).
+pub trait FromRow: Sized {
+    fn from_row(row: &Row) -> Result<Self>;
+}
+
+/// Read the column `name` from `row`, matching the name exactly first and then
+/// case-insensitively, as SQLite identifiers are.
+pub fn column<T: FromSql>(row: &Row, name: &str) -> Result<T> {
+    let stmt = row.as_ref();
+    let names = stmt.column_names();
+    let idx = names
+        .iter()
+        .position(|col| *col == name)
+        .or_else(|| names.iter().position(|col| col.eq_ignore_ascii_case(name)))
+        .ok_or_else(|| PrqliteError::MissingColumn {
+            name: name.to_owned(),
+            available: names.iter().map(|col| col.to_string()).collect(),
+        })?;
+
+    row.get(idx).map_err(|source| PrqliteError::ColumnType {
+        name: name.to_owned(),
+        target: std::any::type_name::<T>(),
+        source,
+    })
+}
+
+/// Implement [`FromRow`] for a struct by mapping each listed field to the column with the
+/// same name, or to the column given after `=`.
+///
+/// ```
+/// use prqlite_rs::impl_from_row;
+///
+/// struct Person {
+///     id: i32,
+///     name: String,
+/// }
+/// impl_from_row!(Person { id, name = "Name" });
+/// ```
+#[macro_export]
+macro_rules! impl_from_row {
+    ($ty:ident { $($field:ident $(= $col:literal)?),* $(,)? }) => {
+        impl $crate::FromRow for $ty {
+            fn from_row(row: &$crate::rusqlite::Row) -> $crate::Result<Self> {
+                Ok(Self {
+                    $($field: $crate::column(row, $crate::impl_from_row!(@col $field $($col)?))?,)*
+                })
+            }
+        }
+    };
+    (@col $field:ident $col:literal) => {
+        $col
+    };
+    (@col $field:ident) => {
+        stringify!($field)
+    };
+}
@@ -0,0 +1,89 @@
+use crate::{prelude::function_declaration, Prqlite, Result};
+use rusqlite::{
+    functions::{Aggregate, Context, FunctionFlags},
+    ToSql,
+};
+use std::panic::{RefUnwindSafe, UnwindSafe};
+
+impl Prqlite {
+    /// Register a scalar SQL function taking `n_args` arguments, `-1` for any number.
+    ///
+    /// Unless it is variadic or its name isn't a plain PRQL identifier, the function is
+    /// also declared to the PRQL compiler so it can be called like any PRQL function,
+    /// e.g. `derive [hash = geohash lat lon]`. Pass
+    /// [`FunctionFlags::SQLITE_DETERMINISTIC`] for functions that always return the same
+    /// result for the same arguments.
+    pub fn register_scalar<F, T>(
+        &self,
+        name: &str,
+        n_args: i32,
+        flags: FunctionFlags,
+        function: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&Context<'_>) -> rusqlite::Result<T> + Send + UnwindSafe + 'static,
+        T: ToSql,
+    {
+        self.conn.create_scalar_function(
+            name,
+            n_args,
+            flags | FunctionFlags::SQLITE_UTF8,
+            function,
+        )?;
+        self.declare_function(name, n_args);
+        Ok(())
+    }
+
+    /// Register an aggregate SQL function taking `n_args` arguments, `-1` for any number,
+    /// declared to the PRQL compiler like [`Prqlite::register_scalar`] so it can be used in
+    /// `aggregate`.
+    pub fn register_aggregate<A, D, T>(
+        &self,
+        name: &str,
+        n_args: i32,
+        flags: FunctionFlags,
+        aggregate: D,
+    ) -> Result<()>
+    where
+        A: RefUnwindSafe + UnwindSafe,
+        D: Aggregate<A, T> + 'static,
+        T: ToSql,
+    {
+        self.conn.create_aggregate_function(
+            name,
+            n_args,
+            flags | FunctionFlags::SQLITE_UTF8,
+            aggregate,
+        )?;
+        self.declare_function(name, n_args);
+        Ok(())
+    }
+
+    /// Remove a function registered with `n_args` arguments.
+    pub fn remove_function(&self, name: &str, n_args: i32) -> Result<()> {
+        self.conn.remove_function(name, n_args)?;
+        self.functions
+            .borrow_mut()
+            .retain(|(n, args, _)| n != name || *args != n_args);
+        self.invalidate_prelude();
+        Ok(())
+    }
+
+    /// PRQL has no overloading, so when a function is registered with several numbers
+    /// of arguments, only the last one registered is declared.
+    fn declare_function(&self, name: &str, n_args: i32) {
+        let mut functions = self.functions.borrow_mut();
+        functions.retain(|(n, args, _)| n != name || *args != n_args);
+        if let Some(declaration) = function_declaration(name, n_args) {
+            functions.push((name.to_owned(), n_args, declaration));
+        }
+        drop(functions);
+        self.invalidate_prelude();
+    }
+
+    /// Drop the prelude, and the queries compiled with it, so it is regenerated.
+    pub(crate) fn invalidate_prelude(&self) {
+        *self.prelude.borrow_mut() = None;
+        self.cache.borrow_mut().clear();
+    }
+}
@@ -0,0 +1,324 @@
+use crate::{schema::quote_identifier, Prqlite, PrqliteError, Result, Transaction};
+use csv::{ReaderBuilder, StringRecord};
+use rusqlite::types::Value;
+use std::{fs::File, io::Read, path::Path};
+
+/// How [`Prqlite::import_csv`] reads a delimited file.
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    delimiter: u8,
+    quote: u8,
+    has_header: bool,
+    sample_size: usize,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            has_header: true,
+            sample_size: 1000,
+        }
+    }
+}
+
+impl CsvOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Options for a file, reading it as TSV when its extension is `.tsv` or `.tab`.
+    pub fn for_path<P: AsRef<Path>>(path: P) -> Self {
+        let mut options = Self::default();
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        if matches!(extension.as_deref(), Some("tsv" | "tab")) {
+            options.delimiter(b'\t');
+        }
+        options
+    }
+    pub fn delimiter(&mut self, delimiter: u8) -> &mut Self {
+        self.delimiter = delimiter;
+        self
+    }
+    pub fn quote(&mut self, quote: u8) -> &mut Self {
+        self.quote = quote;
+        self
+    }
+    /// Whether the first record holds the column names. Without it, columns are named
+    /// `column1`, `column2`...
+    pub fn has_header(&mut self, has_header: bool) -> &mut Self {
+        self.has_header = has_header;
+        self
+    }
+    /// Number of records read to infer the column types of a new table.
+    pub fn sample_size(&mut self, sample_size: usize) -> &mut Self {
+        self.sample_size = sample_size.max(1);
+        self
+    }
+}
+
+/// Outcome of [`Prqlite::import_csv`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportReport {
+    pub table: String,
+    /// Whether the table was created by the import rather than appended to.
+    pub created: bool,
+    /// Names and declared types of the columns of a created table.
+    pub columns: Vec<(String, String)>,
+    pub inserted: usize,
+    pub rejected: Vec<RejectedRow>,
+}
+
+/// A record left out of an import.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedRow {
+    /// 1-based line of the record in the file.
+    pub line: u64,
+    pub reason: String,
+}
+
+impl Prqlite {
+    /// Load a CSV file into `table`, creating it when it doesn't exist.
+    ///
+    /// Column types of a new table are inferred from the first records: `INTEGER` when
+    /// every value is an integer, `REAL` when every value is a number, `TEXT` otherwise.
+    /// Integers with leading zeros or a `+` sign, like ZIP codes, are kept as `TEXT`.
+    /// Rows are appended to an existing table by matching the header to its column names.
+    /// Empty fields are imported as `NULL`.
+    ///
+    /// The import runs in a single savepoint, inside the transaction open on the
+    /// connection if any. Records that can't be parsed, have the
+    /// wrong number of fields or are refused by SQLite are skipped and listed in
+    /// [`ImportReport::rejected`].
+    pub fn import_csv<P: AsRef<Path>>(
+        &self,
+        path: P,
+        table: &str,
+        options: &CsvOptions,
+    ) -> Result<ImportReport> {
+        self.import_csv_from(open_file(path.as_ref())?, table, options)
+    }
+
+    /// Like [`Prqlite::import_csv`], reading CSV from any reader.
+    pub fn import_csv_from<R: Read>(
+        &self,
+        reader: R,
+        table: &str,
+        options: &CsvOptions,
+    ) -> Result<ImportReport> {
+        self.import_csv_into(reader, "main", table, options)
+    }
+
+    /// Import CSV into `table` of the `schema` database.
+    pub(crate) fn import_csv_into<R: Read>(
+        &self,
+        reader: R,
+        schema: &str,
+        table: &str,
+        options: &CsvOptions,
+    ) -> Result<ImportReport> {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(options.delimiter)
+            .quote(options.quote)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(reader);
+        let mut records = reader.records().map(|record| {
+            record.map_err(|err| {
+                let line = err.position().map_or(0, |p| p.line());
+                (line, err.to_string())
+            })
+        });
+        let mut rejected = vec![];
+
+        // Records used for type inference, kept to be inserted first.
+        let mut sample: Vec<(u64, StringRecord)> = vec![];
+        let mut header = None;
+        for record in records.by_ref() {
+            match record {
+                Ok(record) if options.has_header && header.is_none() => {
+                    header = Some(record.iter().map(str::to_owned).collect::<Vec<_>>());
+                }
+                Ok(record) => {
+                    sample.push((record_line(&record), record));
+                    if sample.len() >= options.sample_size {
+                        break;
+                    }
+                }
+                Err((line, reason)) => rejected.push(RejectedRow { line, reason }),
+            }
+        }
+        let width = match &header {
+            Some(header) => header.len(),
+            None => sample.first().map_or(0, |(_, record)| record.len()),
+        };
+        if width == 0 {
+            return Err(PrqliteError::Import(format!(
+                "no columns to import into '{table}'"
+            )));
+        }
+
+        let tx = Transaction::begin_savepoint(self, "prqlite_import")?;
+        let existing = self.table_columns(schema, table)?;
+        let target = qualified_name(schema, table);
+        let created = existing.is_empty();
+        let names = match header {
+            Some(header) => column_names(header),
+            // Without a header, records fill the columns of an existing table in order.
+            None if !created => existing.iter().take(width).cloned().collect(),
+            None => column_names(vec![String::new(); width]),
+        };
+        let columns = if created {
+            let columns = names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| {
+                    let values = sample.iter().map(|(_, record)| record.get(i));
+                    (name.clone(), infer_type(values).to_owned())
+                })
+                .collect::<Vec<_>>();
+            let definitions = columns
+                .iter()
+                .map(|(name, ty)| format!("{} {ty}", quote_identifier(name)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            self.conn
+                .execute_batch(&format!("CREATE TABLE {target} ({definitions})"))?;
+            columns
+        } else {
+            let missing = names
+                .iter()
+                .find(|name| !existing.iter().any(|c| c.eq_ignore_ascii_case(name)));
+            if let Some(missing) = missing {
+                return Err(PrqliteError::Import(format!(
+                    "table '{table}' has no column '{missing}'"
+                )));
+            }
+            if names.len() < width {
+                return Err(PrqliteError::Import(format!(
+                    "table '{table}' has {} columns, the file has {width}",
+                    names.len()
+                )));
+            }
+            vec![]
+        };
+
+        let placeholders = vec!["?"; names.len()].join(", ");
+        let quoted = names
+            .iter()
+            .map(|name| quote_identifier(name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut insert = self.conn.prepare(&format!(
+            "INSERT INTO {target} ({quoted}) VALUES ({placeholders})"
+        ))?;
+
+        let mut inserted = 0;
+        let sample = sample.into_iter().map(Ok);
+        let rest = records.map(|record| record.map(|r| (record_line(&r), r)));
+        for record in sample.chain(rest) {
+            let (line, record) = match record {
+                Ok(record) => record,
+                Err((line, reason)) => {
+                    rejected.push(RejectedRow { line, reason });
+                    continue;
+                }
+            };
+            if record.len() != names.len() {
+                rejected.push(RejectedRow {
+                    line,
+                    reason: format!("expected {} fields, found {}", names.len(), record.len()),
+                });
+                continue;
+            }
+            let values = record.iter().map(|field| match field {
+                "" => Value::Null,
+                field => Value::Text(field.to_owned()),
+            });
+            match insert.execute(rusqlite::params_from_iter(values)) {
+                Ok(_) => inserted += 1,
+                Err(err) => rejected.push(RejectedRow {
+                    line,
+                    reason: err.to_string(),
+                }),
+            }
+        }
+        drop(insert);
+        tx.commit()?;
+
+        Ok(ImportReport {
+            table: table.to_owned(),
+            created,
+            columns,
+            inserted,
+            rejected,
+        })
+    }
+
+    /// Column names of `table` in the `schema` database, empty when there is no such table.
+    pub(crate) fn table_columns(&self, schema: &str, table: &str) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name FROM pragma_table_info(?1, ?2) ORDER BY cid")?;
+        let names = stmt
+            .query_map([table, schema], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(names)
+    }
+}
+
+pub(crate) fn open_file(path: &Path) -> Result<File> {
+    File::open(path).map_err(|err| PrqliteError::Import(format!("{}: {err}", path.display())))
+}
+
+pub(crate) fn qualified_name(schema: &str, table: &str) -> String {
+    format!("{}.{}", quote_identifier(schema), quote_identifier(table))
+}
+
+fn record_line(record: &StringRecord) -> u64 {
+    record.position().map_or(0, |p| p.line())
+}
+
+/// Name the columns from the header, filling blanks and renaming duplicates.
+pub(crate) fn column_names(header: Vec<String>) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+    for (i, name) in header.into_iter().enumerate() {
+        let name = match name.trim() {
+            "" => format!("column{}", i + 1),
+            name => name.to_owned(),
+        };
+        let mut unique = name.clone();
+        let mut n = 2;
+        while names
+            .iter()
+            .any(|other| other.eq_ignore_ascii_case(&unique))
+        {
+            unique = format!("{name}_{n}");
+            n += 1;
+        }
+        names.push(unique);
+    }
+    names
+}
+
+/// Narrowest of `INTEGER`, `REAL` and `TEXT` holding every non-empty value, `TEXT` when
+/// there are none.
+fn infer_type<'a>(values: impl Iterator<Item = Option<&'a str>>) -> &'static str {
+    let mut ty = None;
+    for value in values.flatten().map(str::trim).filter(|v| !v.is_empty()) {
+        match value.parse::<i64>() {
+            // Stored as a number, `007` would read back as `7`.
+            Ok(n) if n.to_string() != value => return "TEXT",
+            Ok(_) => {
+                ty.get_or_insert("INTEGER");
+            }
+            Err(_) if value.parse::<f64>().is_ok_and(f64::is_finite) => ty = Some("REAL"),
+            Err(_) => return "TEXT",
+        }
+    }
+    ty.unwrap_or("TEXT")
+}
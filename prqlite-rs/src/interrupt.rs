@@ -0,0 +1,127 @@
+use crate::Prqlite;
+use rusqlite::InterruptHandle;
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Number of SQLite virtual machine instructions between two deadline checks.
+const PROGRESS_PERIOD: i32 = 1000;
+
+/// Queries running on a connection, shared with the progress handler.
+#[derive(Debug, Default)]
+pub(crate) struct DeadlineState {
+    /// Id of every live [`TimeoutGuard`], i.e. of queries started through the handle and
+    /// not finished yet, along with when the query first ran. Statements run outside of
+    /// them are never interrupted.
+    started: Vec<(u64, Option<Instant>)>,
+    next_id: u64,
+}
+
+pub(crate) type Deadline = Arc<Mutex<DeadlineState>>;
+
+impl Prqlite {
+    /// Handle interrupting the query running on this connection, from any thread. The
+    /// query fails with [`PrqliteError::Interrupted`](crate::PrqliteError::Interrupted).
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.conn.get_interrupt_handle()
+    }
+
+    /// Interrupt queries running longer than `timeout`, or never with `None`.
+    ///
+    /// Time is counted from when a query started through the handle, such as with
+    /// [`Prqlite::query`], [`Prqlite::stream`] or [`Prqlite::execute`], first runs, so rows
+    /// of a streamed result must be read within it too. It stops when the query finishes,
+    /// or when its statement or result is dropped.
+    pub fn set_timeout(&self, timeout: Option<Duration>) {
+        self.timeout.set(timeout);
+        for (_, started) in &mut lock(&self.deadline).started {
+            *started = None;
+        }
+        match timeout {
+            Some(timeout) => {
+                let deadline = self.deadline.clone();
+                self.conn.progress_handler(
+                    PROGRESS_PERIOD,
+                    Some(move || {
+                        // Queries are timed from the first check after they started, and
+                        // interrupted as soon as the oldest one runs out of time.
+                        let now = Instant::now();
+                        let oldest = lock(&deadline)
+                            .started
+                            .iter_mut()
+                            .map(|(_, started)| *started.get_or_insert(now))
+                            .min();
+                        oldest.is_some_and(|started| now >= started + timeout)
+                    }),
+                );
+            }
+            None => self.conn.progress_handler(0, None::<fn() -> bool>),
+        }
+    }
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.get()
+    }
+
+    /// Put a query under the timeout until the returned guard is dropped, its time being
+    /// counted on its own, without restarting the one of the queries already running.
+    pub(crate) fn start_timeout(&self) -> TimeoutGuard {
+        let mut state = lock(&self.deadline);
+        let id = state.next_id;
+        state.next_id += 1;
+        state.started.push((id, None));
+        TimeoutGuard {
+            deadline: self.deadline.clone(),
+            id,
+        }
+    }
+}
+
+fn lock(deadline: &Deadline) -> MutexGuard<'_, DeadlineState> {
+    deadline.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Keeps a query under the timeout of its connection while alive.
+#[derive(Debug)]
+pub(crate) struct TimeoutGuard {
+    deadline: Deadline,
+    id: u64,
+}
+
+impl Drop for TimeoutGuard {
+    fn drop(&mut self) {
+        lock(&self.deadline)
+            .started
+            .retain(|(id, _)| *id != self.id);
+    }
+}
+
+/// A statement prepared by [`Prqlite::execute`] and the like, dereferencing to the
+/// rusqlite statement. It runs under the timeout of the connection until dropped.
+pub struct TimedStatement<S> {
+    stmt: S,
+    _timeout: TimeoutGuard,
+}
+
+impl<S> TimedStatement<S> {
+    pub(crate) fn new(stmt: S, timeout: TimeoutGuard) -> Self {
+        Self {
+            stmt,
+            _timeout: timeout,
+        }
+    }
+}
+
+impl<S> Deref for TimedStatement<S> {
+    type Target = S;
+    fn deref(&self) -> &S {
+        &self.stmt
+    }
+}
+
+impl<S> DerefMut for TimedStatement<S> {
+    fn deref_mut(&mut self) -> &mut S {
+        &mut self.stmt
+    }
+}
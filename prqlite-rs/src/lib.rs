@@ -1,38 +1,302 @@
 #[cfg(test)]
 mod tests;
 
-use anyhow::Result;
+#[cfg(feature = "arrow")]
+mod arrow;
+mod async_prqlite;
+mod backup;
+mod batch;
+mod builder;
+mod cache;
+mod dml;
+mod error;
+mod export;
+mod file_table;
+mod from_row;
+mod functions;
+mod import;
+mod interrupt;
+mod origin;
+mod params;
+mod pool;
+mod prelude;
+mod rows;
+mod schema;
+mod transaction;
+
+#[cfg(feature = "arrow")]
+pub use arrow::{ArrowBatches, RecordBatches};
+#[cfg(feature = "arrow")]
+pub use arrow_array;
+#[cfg(feature = "arrow")]
+pub use arrow_schema;
+pub use async_prqlite::AsyncPrqlite;
+pub use batch::{split_queries, BatchQuery, BatchResult};
+pub use builder::{default_compile_options, PrqliteBuilder};
+pub use cache::CacheStats;
+pub use error::{PrqliteError, Result};
+pub use export::{BlobEncoding, ExportFormat, ExportOptions};
+pub use file_table::FileTableFormat;
+pub use from_row::{column, FromRow};
+pub use import::{CsvOptions, ImportReport, RejectedRow};
+pub use interrupt::TimedStatement;
+pub use origin::{PrqlLocation, QueryOrigin};
+pub use params::QueryParams;
+pub use pool::{PoolBuilder, PooledPrqlite, PrqlitePool};
+pub use prelude::schema_prelude;
+pub use rows::{ColumnInfo, OwnedRows, QueryResult, QueryRows};
+pub use rusqlite;
+pub use rusqlite::backup::Progress as BackupProgress;
+pub use rusqlite::functions::{Aggregate, Context, FunctionFlags};
+pub use rusqlite::{named_params, params, InterruptHandle, TransactionBehavior};
+pub use schema::{Column, Database, ForeignKey, Index, Schema, Table, TableKind};
+pub use transaction::Transaction;
+
+use cache::CompileCache;
+use interrupt::Deadline;
+use params::bind_placeholders;
+use prelude::{strip_prelude, with_prelude};
 use prql_compiler::{compile, Options};
-use rusqlite::{Connection, Statement};
+use rusqlite::{CachedStatement, Connection, Statement};
+use std::{
+    cell::{Cell, RefCell},
+    sync::Arc,
+    time::Duration,
+};
 
 #[derive(Debug)]
 pub struct Prqlite {
     conn: Connection,
+    compile_options: Options,
+    /// `compile_options` targeting SQLite, used for the queries run against the database.
+    sqlite_options: Options,
+    /// Key of `sqlite_options` in the compile cache.
+    options_key: Arc<str>,
+    cache: RefCell<CompileCache>,
+    declare_schema: bool,
+    /// Schema prelude along with the `(main, temp)` schema versions it was generated for.
+    prelude: RefCell<Option<((i64, i64), String)>>,
+    /// PRQL declarations of the registered functions, by function name and number of
+    /// arguments, in order of registration.
+    functions: RefCell<Vec<(String, i32, String)>>,
+    timeout: Cell<Option<Duration>>,
+    deadline: Deadline,
 }
 impl Prqlite {
     pub fn open(path: &str) -> Result<Self> {
-        Ok(Self {
-            conn: Connection::open(path)?,
-        })
+        Self::builder().path(path).open()
+    }
+    pub fn builder() -> PrqliteBuilder {
+        PrqliteBuilder::new()
+    }
+    /// Options used by [`Prqlite::compile`].
+    pub fn compile_options(&self) -> &Options {
+        &self.compile_options
+    }
+    /// Compile PRQL into SQL with the handle's compile options, e.g. for logging.
+    pub fn compile(&self, prql: &str) -> Result<String> {
+        self.refresh_prelude()?;
+        self.compile_with_prelude(prql, &self.compile_options)
+    }
+    /// Compile PRQL into SQL that can be run against the database: the handle's options,
+    /// always targeting SQLite. Results are kept in the compile cache.
+    fn compile_for_sqlite(&self, prql: &str) -> Result<String> {
+        let _timeout = self.start_timeout();
+        self.refresh_prelude()?;
+        self.cache
+            .borrow_mut()
+            .get_or_compile(prql, &self.options_key, || {
+                self.compile_with_prelude(prql, &self.sqlite_options)
+            })
+    }
+    fn compile_with_prelude(&self, prql: &str, options: &Options) -> Result<String> {
+        match &*self.prelude.borrow() {
+            Some((_, prelude)) => compile(&with_prelude(prelude, prql), options)
+                .map_err(|err| strip_prelude(err, prelude, prql, options.color).into()),
+            None => Ok(compile(prql, options)?),
+        }
+    }
+    /// Regenerate the prelude when the schema changed since it was generated, dropping
+    /// the queries compiled against the old one.
+    fn refresh_prelude(&self) -> Result<()> {
+        let _timeout = self.start_timeout();
+        if !self.declare_schema && self.functions.borrow().is_empty() {
+            return Ok(());
+        }
+        let version = match self.declare_schema {
+            true => (
+                self.conn
+                    .query_row("PRAGMA main.schema_version", [], |row| row.get(0))?,
+                self.conn
+                    .query_row("PRAGMA temp.schema_version", [], |row| row.get(0))?,
+            ),
+            false => (0, 0),
+        };
+        if matches!(&*self.prelude.borrow(), Some((v, _)) if *v == version) {
+            return Ok(());
+        }
+        // Only the last registered declaration of a name, as later ones would shadow it.
+        let functions = self.functions.borrow();
+        let mut prelude: String = functions
+            .iter()
+            .enumerate()
+            .filter(|(idx, (name, ..))| !functions[idx + 1..].iter().any(|(n, ..)| n == name))
+            .map(|(_, (.., declaration))| declaration.as_str())
+            .collect();
+        drop(functions);
+        if self.declare_schema {
+            prelude.push_str(&schema_prelude(&self.schema()?));
+        }
+        self.cache.borrow_mut().clear();
+        *self.prelude.borrow_mut() = Some((version, prelude));
+        Ok(())
+    }
+    /// PRQL declarations prepended to every query: the registered functions, and the
+    /// database tables when enabled with [`PrqliteBuilder::declare_schema`].
+    pub fn prelude(&self) -> Result<Option<String>> {
+        self.refresh_prelude()?;
+        Ok(self.prelude.borrow().as_ref().map(|(_, p)| p.clone()))
+    }
+    /// Hit/miss counters and size of the compile cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.borrow().stats()
+    }
+    /// Drop every compiled query and prepared statement kept for reuse.
+    pub fn clear_cache(&self) {
+        self.cache.borrow_mut().clear();
+        self.conn.flush_prepared_statement_cache();
     }
-    pub fn execute(&self, prql: &str) -> Result<Statement> {
-        let sql = compile(prql, &Options::default().no_format().no_signature())?;
-        let stmt = self.conn.prepare(&sql)?;
-        Ok(stmt)
+    /// Prepare SQL compiled from `prql`, pointing SQLite errors back at the PRQL source.
+    fn prepare_compiled(&self, prql: &str, sql: &str) -> Result<CachedStatement<'_>> {
+        self.conn
+            .prepare_cached(sql)
+            .map_err(|err| PrqliteError::from(err).with_origin(prql, sql))
     }
-    pub fn execute_with_sql(&self, sql: &str) -> Result<Statement> {
+    pub fn execute(&self, prql: &str) -> Result<TimedStatement<CachedStatement<'_>>> {
+        let sql = self.compile_for_sqlite(prql)?;
+        let stmt = self.prepare_compiled(prql, &sql)?;
+        Ok(TimedStatement::new(stmt, self.start_timeout()))
+    }
+    /// Compile a PRQL query containing `$1`/`$name` parameters and bind `params` to them.
+    ///
+    /// The returned statement already has its parameters bound, so it must be run with
+    /// [`Statement::raw_query`] or [`Statement::raw_execute`].
+    pub fn execute_with_params<P: QueryParams>(
+        &self,
+        prql: &str,
+        params: P,
+    ) -> Result<TimedStatement<CachedStatement<'_>>> {
+        let sql = bind_placeholders(&self.compile_for_sqlite(prql)?);
+        let mut stmt = self.prepare_compiled(prql, &sql)?;
+        params.bind(&mut stmt)?;
+        Ok(TimedStatement::new(stmt, self.start_timeout()))
+    }
+    /// Run a PRQL query and load its whole result.
+    pub fn query(&self, prql: &str) -> Result<OwnedRows> {
+        let sql = self.compile_for_sqlite(prql)?;
+        self.collect_rows(prql, &sql)
+    }
+    /// Prepare a PRQL query whose rows are read one at a time, without loading the whole
+    /// result in memory.
+    pub fn stream(&self, prql: &str) -> Result<QueryResult<'_>> {
+        let sql = self.compile_for_sqlite(prql)?;
+        let stmt = self.prepare_compiled(prql, &sql)?;
+        Ok(QueryResult::new(
+            &self.conn,
+            stmt,
+            &sql,
+            self.start_timeout(),
+        ))
+    }
+    /// [`Prqlite::stream`] a PRQL query containing `$1`/`$name` parameters bound to `params`.
+    pub fn stream_with_params<P: QueryParams>(
+        &self,
+        prql: &str,
+        params: P,
+    ) -> Result<QueryResult<'_>> {
+        let sql = bind_placeholders(&self.compile_for_sqlite(prql)?);
+        let mut stmt = self.prepare_compiled(prql, &sql)?;
+        params.bind(&mut stmt)?;
+        Ok(QueryResult::new(
+            &self.conn,
+            stmt,
+            &sql,
+            self.start_timeout(),
+        ))
+    }
+    /// [`Prqlite::stream`] a SQL statement.
+    pub fn stream_with_sql(&self, sql: &str) -> Result<QueryResult<'_>> {
+        let timeout = self.start_timeout();
+        let stmt = self.conn.prepare_cached(sql)?;
+        Ok(QueryResult::new(&self.conn, stmt, sql, timeout))
+    }
+    /// Run a PRQL query and map every row of the result into `T` by column name.
+    pub fn query_as<T: FromRow>(&self, prql: &str) -> Result<Vec<T>> {
+        let mut stmt = self.execute(prql)?;
+        let mut rows = stmt.query([])?;
+        let mut out = vec![];
+        while let Some(row) = rows.next()? {
+            out.push(T::from_row(row)?);
+        }
+        Ok(out)
+    }
+    pub fn execute_with_sql(&self, sql: &str) -> Result<TimedStatement<Statement<'_>>> {
+        let timeout = self.start_timeout();
         let stmt = self.conn.prepare(sql)?;
-        Ok(stmt)
+        Ok(TimedStatement::new(stmt, timeout))
     }
-    pub fn execute_batch(&self, prql: &str) -> Result<()> {
-        let mut queries: Vec<String> = vec![];
-        for query in prql.split(";").into_iter() {
-            let sql = compile(query, &Options::default().no_format().no_signature())?;
-            queries.push(sql);
+    /// Split a PRQL script into queries with [`split_queries`] and run them one by one.
+    ///
+    /// Every query is compiled before any of them runs. A failure is reported as
+    /// [`PrqliteError::Batch`], pointing at the query and the lines it spans.
+    pub fn execute_batch(&self, prql: &str) -> Result<Vec<BatchResult>> {
+        let queries = split_queries(prql);
+        let batch_err = |index: usize, query: &BatchQuery, err: PrqliteError| PrqliteError::Batch {
+            index,
+            start_line: query.start_line,
+            end_line: query.end_line,
+            source: Box::new(err),
+        };
+
+        let mut compiled = vec![];
+        for (index, query) in queries.iter().enumerate() {
+            let sql = self
+                .compile_for_sqlite(&query.source)
+                .map_err(|err| batch_err(index, query, err))?;
+            compiled.push(sql);
+        }
+
+        let mut results = vec![];
+        for (index, (query, sql)) in queries.into_iter().zip(compiled).enumerate() {
+            let OwnedRows { columns, rows } = self
+                .collect_rows(&query.source, &sql)
+                .map_err(|err| batch_err(index, &query, err))?;
+            results.push(BatchResult {
+                query,
+                sql,
+                columns,
+                rows,
+            });
         }
-        Ok(self.conn.execute_batch(&queries.join(";"))?)
+        Ok(results)
+    }
+    fn collect_rows(&self, prql: &str, sql: &str) -> Result<OwnedRows> {
+        let _timeout = self.start_timeout();
+        let mut stmt = self.prepare_compiled(prql, sql)?;
+        OwnedRows::collect(&mut stmt).map_err(|err| PrqliteError::from(err).with_origin(prql, sql))
+    }
+    /// Start a deferred transaction. See [`Transaction`].
+    pub fn transaction(&self) -> Result<Transaction<'_>> {
+        Transaction::begin(self, TransactionBehavior::Deferred)
+    }
+    pub fn transaction_with_behavior(
+        &self,
+        behavior: TransactionBehavior,
+    ) -> Result<Transaction<'_>> {
+        Transaction::begin(self, behavior)
     }
     pub fn execute_batch_with_sql(&self, sql: &str) -> Result<()> {
+        let _timeout = self.start_timeout();
         Ok(self.conn.execute_batch(sql)?)
     }
     pub fn get_conn(&self) -> Option<&str> {
@@ -0,0 +1,143 @@
+use std::fmt::{self, Display, Formatter};
+
+/// Where a SQLite error on SQL compiled from PRQL comes from, attached to
+/// [`PrqliteError::Sqlite`](crate::PrqliteError::Sqlite).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryOrigin {
+    /// The SQL generated from the PRQL query.
+    pub sql: String,
+    /// Token of the generated SQL that SQLite complained about.
+    pub sql_fragment: Option<String>,
+    /// Position of that token in the PRQL source, on a best-effort basis: the token is
+    /// searched for in the PRQL text, so it is only reported when it occurs exactly once
+    /// there. It may still point at a same-named word unrelated to the error.
+    pub prql_location: Option<PrqlLocation>,
+}
+
+/// A position within a PRQL query. Line and column are 0-based, like the locations of
+/// PRQL compile errors, and counted in characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrqlLocation {
+    pub line: usize,
+    pub column: usize,
+    pub len: usize,
+    /// The whole PRQL line holding the token.
+    pub source_line: String,
+}
+
+impl PrqlLocation {
+    /// The PRQL line followed by a caret under the token.
+    pub fn caret(&self) -> String {
+        let gutter = format!("{} | ", self.line + 1);
+        format!(
+            "{gutter}{}\n{}{}",
+            self.source_line,
+            " ".repeat(gutter.len() + self.column),
+            "^".repeat(self.len.max(1))
+        )
+    }
+}
+
+impl Display for PrqlLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line + 1, self.column + 1)
+    }
+}
+
+impl QueryOrigin {
+    /// Find what SQLite complained about in `sql`, from the error offset or message, and
+    /// look the same token up in `prql`.
+    pub(crate) fn locate(err: &rusqlite::Error, prql: &str, sql: &str) -> Self {
+        let sql_fragment = match err {
+            rusqlite::Error::SqlInputError { msg, offset, .. } => usize::try_from(*offset)
+                .ok()
+                .and_then(|offset| token_at(sql, offset))
+                .or_else(|| token_in_message(msg)),
+            rusqlite::Error::SqliteFailure(_, Some(msg)) => token_in_message(msg),
+            _ => None,
+        };
+        let prql_location = sql_fragment
+            .as_deref()
+            .and_then(|fragment| find_in_prql(prql, fragment));
+
+        Self {
+            sql: sql.to_owned(),
+            sql_fragment,
+            prql_location,
+        }
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The SQL token starting at byte `offset`, without its quotes.
+fn token_at(sql: &str, offset: usize) -> Option<String> {
+    let rest = sql.get(offset..)?;
+    let token = match rest.chars().next()? {
+        q @ ('"' | '`' | '[') => {
+            let close = if q == '[' { ']' } else { q };
+            rest[1..].split(close).next()?.to_owned()
+        }
+        _ => rest.chars().take_while(|c| is_ident_char(*c)).collect(),
+    };
+    (!token.is_empty()).then_some(token)
+}
+
+/// The name SQLite quotes in messages like `no such column: t.name` or
+/// `near "FROM": syntax error`.
+fn token_in_message(msg: &str) -> Option<String> {
+    if let Some(rest) = msg.strip_prefix("near \"") {
+        return rest.split('"').next().map(str::to_owned);
+    }
+    for prefix in ["no such column: ", "no such table: ", "no such function: "] {
+        if let Some(name) = msg.strip_prefix(prefix) {
+            return name.rsplit('.').next().map(|name| name.trim().to_owned());
+        }
+    }
+    None
+}
+
+/// The only whole-word occurrence of `fragment` in `prql` outside comments, matched
+/// exactly or else case-insensitively. `None` when there are several, as the token
+/// can't be told apart.
+fn find_in_prql(prql: &str, fragment: &str) -> Option<PrqlLocation> {
+    let fragment: Vec<char> = fragment.chars().collect();
+    let exact = |a: &[char]| a == fragment.as_slice();
+    let loose = |a: &[char]| {
+        a.iter()
+            .zip(&fragment)
+            .all(|(a, b)| a.to_lowercase().eq(b.to_lowercase()))
+    };
+
+    for matches in [&exact as &dyn Fn(&[char]) -> bool, &loose] {
+        let mut found = vec![];
+        for (line_idx, line) in prql.lines().enumerate() {
+            let code = line.split('#').next().unwrap_or_default();
+            let chars: Vec<char> = code.chars().collect();
+            if chars.len() < fragment.len() {
+                continue;
+            }
+            for column in 0..=chars.len() - fragment.len() {
+                let end = column + fragment.len();
+                let bounded = (column == 0 || !is_ident_char(chars[column - 1]))
+                    && (end == chars.len() || !is_ident_char(chars[end]));
+                if bounded && matches(&chars[column..end]) {
+                    found.push(PrqlLocation {
+                        line: line_idx,
+                        column,
+                        len: fragment.len(),
+                        source_line: line.to_owned(),
+                    });
+                }
+            }
+        }
+        match found.len() {
+            0 => continue,
+            1 => return found.pop(),
+            _ => return None,
+        }
+    }
+    None
+}
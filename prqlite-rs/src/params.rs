@@ -0,0 +1,98 @@
+use crate::{PrqliteError, Result};
+use rusqlite::{Statement, ToSql};
+
+/// Values that can be bound to the placeholders of a compiled PRQL query.
+///
+/// PRQL positional parameters (`$1`, `$2`, ...) are bound from a slice such as the one
+/// produced by [`rusqlite::params!`], and named parameters (`$name`) from a slice of
+/// `(name, value)` pairs such as the one produced by [`rusqlite::named_params!`].
+/// Every parameter of the query must be given a value: a positional slice holds exactly
+/// one value per parameter, and a named slice one for each name.
+/// Names may be passed with or without the leading `$`.
+pub trait QueryParams {
+    fn bind(self, stmt: &mut Statement) -> Result<()>;
+}
+
+impl QueryParams for &[&dyn ToSql] {
+    fn bind(self, stmt: &mut Statement) -> Result<()> {
+        let expected = stmt.parameter_count();
+        if self.len() != expected {
+            return Err(PrqliteError::Parameter(format!(
+                "query expects {expected} parameter(s), but {} were passed",
+                self.len()
+            )));
+        }
+        for (idx, value) in self.iter().enumerate() {
+            stmt.raw_bind_parameter(idx + 1, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl QueryParams for &[(&str, &dyn ToSql)] {
+    fn bind(self, stmt: &mut Statement) -> Result<()> {
+        let mut bound = vec![false; stmt.parameter_count()];
+        for (name, value) in self.iter() {
+            let name = match name.starts_with(['$', ':', '@', '?']) {
+                true => name.to_string(),
+                false => format!("${name}"),
+            };
+            match stmt.parameter_index(&name)? {
+                Some(idx) => {
+                    stmt.raw_bind_parameter(idx, value)?;
+                    bound[idx - 1] = true;
+                }
+                None => {
+                    return Err(PrqliteError::Parameter(format!(
+                        "query has no parameter named '{name}'"
+                    )))
+                }
+            }
+        }
+        match bound.iter().position(|bound| !bound) {
+            Some(idx) => Err(PrqliteError::Parameter(format!(
+                "no value was passed for parameter '{}'",
+                stmt.parameter_name(idx + 1).unwrap_or("?")
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Rewrite positional PRQL parameters (`$1`) in compiled SQL into SQLite's numbered
+/// placeholders (`?1`), so they bind by position rather than by order of appearance.
+/// Named parameters (`$name`) are already valid SQLite placeholders and are kept as is.
+pub(crate) fn bind_placeholders(sql: &str) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut chars = sql.chars().peekable();
+    let mut quote: Option<char> = None;
+
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) => {
+                if c == q {
+                    quote = None;
+                }
+                out.push(c);
+            }
+            None => match c {
+                '\'' | '"' | '`' => {
+                    quote = Some(c);
+                    out.push(c);
+                }
+                '[' => {
+                    quote = Some(']');
+                    out.push(c);
+                }
+                '$' if chars.peek().is_some_and(char::is_ascii_digit) => {
+                    out.push('?');
+                    while let Some(d) = chars.next_if(char::is_ascii_digit) {
+                        out.push(d);
+                    }
+                }
+                _ => out.push(c),
+            },
+        }
+    }
+    out
+}
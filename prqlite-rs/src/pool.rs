@@ -0,0 +1,279 @@
+use crate::{Prqlite, PrqliteBuilder, PrqliteError, Result};
+use std::{
+    fmt::{self, Debug, Formatter},
+    ops::Deref,
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    time::Duration,
+};
+
+const DEFAULT_READERS: usize = 4;
+const DEFAULT_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+type InitHook = Arc<dyn Fn(&Prqlite) -> Result<()> + Send + Sync>;
+
+/// Pool of connections to a database file in WAL mode: one writer and several
+/// read-only readers, so reads run concurrently with each other and with the writer.
+///
+/// The pool is cheap to clone and can be shared between threads.
+#[derive(Debug, Clone)]
+pub struct PrqlitePool {
+    inner: Arc<PoolInner>,
+}
+
+#[derive(Debug)]
+struct PoolInner {
+    readers: Slots,
+    writer: Slots,
+    size: usize,
+}
+
+/// Idle connections of one kind, and a condition variable signalled when one is returned.
+#[derive(Debug)]
+struct Slots {
+    idle: Mutex<Vec<Prqlite>>,
+    returned: Condvar,
+}
+
+impl Slots {
+    fn new(connections: Vec<Prqlite>) -> Self {
+        Self {
+            idle: Mutex::new(connections),
+            returned: Condvar::new(),
+        }
+    }
+    fn take(&self) -> Prqlite {
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            if let Some(prqlite) = idle.pop() {
+                return prqlite;
+            }
+            idle = self
+                .returned
+                .wait(idle)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+    fn try_take(&self) -> Option<Prqlite> {
+        self.idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop()
+    }
+    fn put(&self, prqlite: Prqlite) {
+        self.idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(prqlite);
+        self.returned.notify_one();
+    }
+    fn len(&self) -> usize {
+        self.idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+}
+
+impl PrqlitePool {
+    /// Start configuring a pool over the database file at `path`.
+    pub fn builder(path: &str) -> PoolBuilder {
+        PoolBuilder::new(path)
+    }
+    /// Open a pool with the default settings.
+    pub fn open(path: &str) -> Result<Self> {
+        Self::builder(path).open()
+    }
+
+    /// Borrow a read-only connection, waiting for one to be returned if all are in use.
+    pub fn reader(&self) -> PooledPrqlite {
+        self.pooled(self.inner.readers.take(), false)
+    }
+    /// Borrow a read-only connection if one is idle.
+    pub fn try_reader(&self) -> Option<PooledPrqlite> {
+        let prqlite = self.inner.readers.try_take()?;
+        Some(self.pooled(prqlite, false))
+    }
+    /// Borrow the writer connection, waiting for it to be returned if it is in use.
+    pub fn writer(&self) -> PooledPrqlite {
+        self.pooled(self.inner.writer.take(), true)
+    }
+    /// Borrow the writer connection if it is idle.
+    pub fn try_writer(&self) -> Option<PooledPrqlite> {
+        let prqlite = self.inner.writer.try_take()?;
+        Some(self.pooled(prqlite, true))
+    }
+
+    /// Number of reader connections.
+    pub fn size(&self) -> usize {
+        self.inner.size
+    }
+    /// Number of reader connections currently not borrowed.
+    pub fn idle_readers(&self) -> usize {
+        self.inner.readers.len()
+    }
+
+    fn pooled(&self, prqlite: Prqlite, writer: bool) -> PooledPrqlite {
+        PooledPrqlite {
+            prqlite: Some(prqlite),
+            pool: self.inner.clone(),
+            writer,
+        }
+    }
+}
+
+/// Connection borrowed from a [`PrqlitePool`], returned to it on drop.
+///
+/// It dereferences to [`Prqlite`], so queries go through the usual `execute*` methods.
+pub struct PooledPrqlite {
+    prqlite: Option<Prqlite>,
+    pool: Arc<PoolInner>,
+    writer: bool,
+}
+
+impl PooledPrqlite {
+    /// Whether this is the writer connection of the pool.
+    pub fn is_writer(&self) -> bool {
+        self.writer
+    }
+}
+
+impl Deref for PooledPrqlite {
+    type Target = Prqlite;
+    fn deref(&self) -> &Prqlite {
+        self.prqlite
+            .as_ref()
+            .expect("pooled connection used after being returned")
+    }
+}
+
+impl Drop for PooledPrqlite {
+    fn drop(&mut self) {
+        if let Some(prqlite) = self.prqlite.take() {
+            match self.writer {
+                true => self.pool.writer.put(prqlite),
+                false => self.pool.readers.put(prqlite),
+            }
+        }
+    }
+}
+
+impl Debug for PooledPrqlite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledPrqlite")
+            .field("prqlite", &self.prqlite)
+            .field("writer", &self.writer)
+            .finish()
+    }
+}
+
+/// Builder for [`PrqlitePool`], created by [`PrqlitePool::builder`].
+#[derive(Clone)]
+pub struct PoolBuilder {
+    path: String,
+    connection: PrqliteBuilder,
+    readers: usize,
+    busy_timeout: Duration,
+    init: Vec<InitHook>,
+}
+
+impl PoolBuilder {
+    fn new(path: &str) -> Self {
+        Self {
+            path: path.to_owned(),
+            connection: Prqlite::builder(),
+            readers: DEFAULT_READERS,
+            busy_timeout: DEFAULT_BUSY_TIMEOUT,
+            init: vec![],
+        }
+    }
+    /// Settings of every connection of the pool, such as compile options. Its path is
+    /// replaced by the one of the pool.
+    pub fn connection(&mut self, builder: PrqliteBuilder) -> &mut Self {
+        self.connection = builder;
+        self
+    }
+    /// Number of read-only connections, at least one.
+    pub fn readers(&mut self, readers: usize) -> &mut Self {
+        self.readers = readers;
+        self
+    }
+    /// How long a connection waits for a lock held by another one before failing with
+    /// `SQLITE_BUSY`.
+    pub fn busy_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.busy_timeout = timeout;
+        self
+    }
+    /// Run `init` on every connection once it's opened, e.g. to set pragmas. It runs on
+    /// readers before they are made read-only.
+    pub fn on_connect<F>(&mut self, init: F) -> &mut Self
+    where
+        F: Fn(&Prqlite) -> Result<()> + Send + Sync + 'static,
+    {
+        self.init.push(Arc::new(init));
+        self
+    }
+
+    pub fn open(&self) -> Result<PrqlitePool> {
+        if self.path.is_empty() || self.path == ":memory:" {
+            return Err(PrqliteError::Pool(
+                "a connection pool needs a database file, in-memory databases aren't shared \
+                 between connections"
+                    .to_owned(),
+            ));
+        }
+        if self.readers == 0 {
+            return Err(PrqliteError::Pool(
+                "a connection pool needs at least one reader".to_owned(),
+            ));
+        }
+
+        let writer = self.connect()?;
+        let journal_mode: String =
+            writer
+                .conn
+                .query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))?;
+        if !journal_mode.eq_ignore_ascii_case("wal") {
+            return Err(PrqliteError::Pool(format!(
+                "cannot enable WAL mode on '{}', journal mode is '{journal_mode}'",
+                self.path
+            )));
+        }
+
+        let readers = (0..self.readers)
+            .map(|_| {
+                let reader = self.connect()?;
+                reader.execute_batch_with_sql("PRAGMA query_only = ON")?;
+                Ok(reader)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(PrqlitePool {
+            inner: Arc::new(PoolInner {
+                readers: Slots::new(readers),
+                writer: Slots::new(vec![writer]),
+                size: self.readers,
+            }),
+        })
+    }
+
+    fn connect(&self) -> Result<Prqlite> {
+        let prqlite = self.connection.clone().path(&self.path).open()?;
+        prqlite.conn.busy_timeout(self.busy_timeout)?;
+        for init in &self.init {
+            init(&prqlite)?;
+        }
+        Ok(prqlite)
+    }
+}
+
+impl Debug for PoolBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolBuilder")
+            .field("path", &self.path)
+            .field("connection", &self.connection)
+            .field("readers", &self.readers)
+            .field("busy_timeout", &self.busy_timeout)
+            .field("init", &self.init.len())
+            .finish()
+    }
+}
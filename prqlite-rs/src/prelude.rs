@@ -0,0 +1,196 @@
+use crate::Schema;
+use prql_compiler::ErrorMessages;
+
+/// Names of the PRQL standard library and keywords. A table or column with one of these
+/// names would shadow them when declared, so such tables are left undeclared.
+const RESERVED_NAMES: &[&str] = &[
+    "_eq",
+    "_is_null",
+    "aggregate",
+    "all",
+    "append",
+    "as",
+    "average",
+    "avg",
+    "case",
+    "count",
+    "count_distinct",
+    "derive",
+    "false",
+    "filter",
+    "first",
+    "from",
+    "from_text",
+    "func",
+    "group",
+    "in",
+    "intersect",
+    "join",
+    "lag",
+    "last",
+    "lead",
+    "let",
+    "loop",
+    "lower",
+    "map",
+    "max",
+    "min",
+    "null",
+    "prql",
+    "rank",
+    "rank_dense",
+    "read_csv",
+    "read_parquet",
+    "remove",
+    "round",
+    "row_number",
+    "select",
+    "sort",
+    "stddev",
+    "sum",
+    "switch",
+    "take",
+    "that",
+    "this",
+    "true",
+    "upper",
+    "window",
+    "zip",
+];
+
+/// Generate a PRQL declaration for every table and view of the `temp` and `main`
+/// databases, listing their columns so the compiler can check column references.
+///
+/// Tables are declared in the order SQLite resolves unqualified names: `temp` first.
+pub fn schema_prelude(schema: &Schema) -> String {
+    let mut declared: Vec<&str> = vec![];
+    let mut prelude = String::new();
+
+    for database in ["temp", "main"] {
+        let Some(database) = schema.database(database) else {
+            continue;
+        };
+        for table in &database.tables {
+            let declarable = !table.columns.is_empty()
+                && !is_reserved(&table.name)
+                && !table.name.contains('`')
+                && !database.name.contains('`')
+                && table
+                    .columns
+                    .iter()
+                    .all(|col| !is_reserved(&col.name) && !col.name.contains('`'));
+            if !declarable
+                || declared
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(&table.name))
+            {
+                continue;
+            }
+            declared.push(&table.name);
+
+            let columns = table
+                .columns
+                .iter()
+                .map(|col| identifier(&col.name))
+                .collect::<Vec<_>>()
+                .join(", ");
+            prelude.push_str(&format!(
+                "let {} = (from `{}.{}` | select [{columns}])\n",
+                identifier(&table.name),
+                database.name,
+                table.name
+            ));
+        }
+    }
+    prelude
+}
+
+/// PRQL declaration of the SQL function `name` taking `n_args` arguments, so it can be
+/// called like a PRQL function. Variadic functions and names that aren't plain PRQL
+/// identifiers, or that shadow the standard library, can't be declared.
+pub(crate) fn function_declaration(name: &str, n_args: i32) -> Option<String> {
+    if n_args < 0 || is_reserved(name) || identifier(name) != name {
+        return None;
+    }
+    let args: Vec<String> = (0..n_args).map(|idx| format!("arg{idx}")).collect();
+    let call = args
+        .iter()
+        .map(|arg| format!("{{{arg}}}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(format!(
+        "func {name} {}-> s\"{name}({call})\"\n",
+        args.iter().map(|arg| format!("{arg} ")).collect::<String>()
+    ))
+}
+
+/// Put `prelude` in front of the query, after its `prql` header when it has one, as the
+/// header must come first.
+pub(crate) fn with_prelude(prelude: &str, prql: &str) -> String {
+    let (header, query) = prql.split_at(header_len(prql));
+    format!("{header}{prelude}{query}")
+}
+
+/// Length in bytes of the `prql` header line of a query, along with the blank and comment
+/// lines before it, `0` without a header.
+fn header_len(prql: &str) -> usize {
+    let mut len = 0;
+    for line in prql.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            len += line.len();
+            continue;
+        }
+        let header = trimmed
+            .strip_prefix("prql")
+            .is_some_and(|rest| rest.starts_with(char::is_whitespace));
+        return match header && line.ends_with('\n') {
+            true => len + line.len(),
+            false => 0,
+        };
+    }
+    0
+}
+
+/// Move the spans of compile errors raised for the query [`with_prelude`] back onto
+/// `prql`, and render them against it.
+pub(crate) fn strip_prelude(
+    mut err: ErrorMessages,
+    prelude: &str,
+    prql: &str,
+    color: bool,
+) -> ErrorMessages {
+    let header = prql[..header_len(prql)].chars().count();
+    let offset = prelude.chars().count();
+    for message in &mut err.inner {
+        message.span = message.span.and_then(|mut span| {
+            if span.end <= header {
+                return Some(span);
+            }
+            span.start = span.start.checked_sub(offset).filter(|s| *s >= header)?;
+            span.end = span.end.checked_sub(offset)?;
+            Some(span)
+        });
+        message.location = None;
+        message.display = None;
+    }
+    err.composed("", prql, color)
+}
+
+fn is_reserved(name: &str) -> bool {
+    RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(name))
+}
+
+fn identifier(name: &str) -> String {
+    let mut chars = name.chars();
+    let plain = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    match plain {
+        true => name.to_owned(),
+        false => format!("`{name}`"),
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{interrupt::TimeoutGuard, Result};
+use rusqlite::{ffi, types::Value, CachedStatement, Connection};
+use std::{
+    cell::OnceCell,
+    ffi::{c_char, c_int, CStr},
+};
+
+/// Every row of a query result, detached from the connection.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OwnedRows {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl OwnedRows {
+    pub(crate) fn collect(stmt: &mut rusqlite::Statement) -> rusqlite::Result<Self> {
+        let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+        let mut rows = stmt.raw_query();
+        let mut out = vec![];
+        while let Some(row) = rows.next()? {
+            out.push(row_values(row, columns.len())?);
+        }
+        Ok(Self { columns, rows: out })
+    }
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+/// A column of a [`QueryResult`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnInfo {
+    pub name: String,
+    /// Type the column was declared with, when it comes straight from a table column.
+    pub decl_type: Option<String>,
+    /// Database, table and column name the column comes from, when it comes straight
+    /// from a table column rather than from an expression.
+    pub database: Option<String>,
+    pub table: Option<String>,
+    pub origin_name: Option<String>,
+}
+
+/// A prepared query, created by [`Prqlite::stream`](crate::Prqlite::stream), whose rows
+/// are read one at a time as owned values.
+///
+/// ```
+/// # fn main() -> prqlite_rs::Result<()> {
+/// # let conn = prqlite_rs::Prqlite::open(":memory:")?;
+/// # conn.execute_batch_with_sql("CREATE TABLE persons (id integer)")?;
+/// let mut result = conn.stream("from persons")?;
+/// for row in &mut result {
+///     let row: Vec<prqlite_rs::rusqlite::types::Value> = row?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct QueryResult<'conn> {
+    conn: &'conn Connection,
+    stmt: CachedStatement<'conn>,
+    sql: String,
+    names: Vec<String>,
+    /// Column metadata, read when first asked for.
+    columns: OnceCell<Vec<ColumnInfo>>,
+    _timeout: TimeoutGuard,
+}
+
+impl<'conn> QueryResult<'conn> {
+    pub(crate) fn new(
+        conn: &'conn Connection,
+        stmt: CachedStatement<'conn>,
+        sql: &str,
+        timeout: TimeoutGuard,
+    ) -> Self {
+        let names = stmt.column_names().into_iter().map(str::to_owned).collect();
+        Self {
+            conn,
+            stmt,
+            sql: sql.to_owned(),
+            names,
+            columns: OnceCell::new(),
+            _timeout: timeout,
+        }
+    }
+    pub fn columns(&self) -> &[ColumnInfo] {
+        self.columns.get_or_init(|| {
+            let origins = column_origins(self.conn, &self.sql);
+            self.stmt
+                .columns()
+                .into_iter()
+                .enumerate()
+                .map(|(idx, column)| {
+                    let [database, table, origin_name] =
+                        origins.get(idx).cloned().unwrap_or_default();
+                    ColumnInfo {
+                        name: column.name().to_owned(),
+                        decl_type: column.decl_type().map(str::to_owned),
+                        database,
+                        table,
+                        origin_name,
+                    }
+                })
+                .collect()
+        })
+    }
+    pub fn column_names(&self) -> Vec<&str> {
+        self.names.iter().map(String::as_str).collect()
+    }
+    /// Run the statement, calling `f` with the column index and type of every value
+    /// without loading them.
+    #[cfg(feature = "arrow")]
+    pub(crate) fn scan_types(
+        &mut self,
+        mut f: impl FnMut(usize, rusqlite::types::Type),
+    ) -> Result<()> {
+        let mut rows = self.stmt.raw_query();
+        while let Some(row) = rows.next()? {
+            for idx in 0..self.names.len() {
+                f(idx, row.get_ref(idx)?.data_type());
+            }
+        }
+        Ok(())
+    }
+    /// Whether running the statement leaves the database unchanged.
+    pub fn readonly(&self) -> bool {
+        self.stmt.readonly()
+    }
+    /// Iterate over the rows. The statement runs from the start on every call.
+    pub fn rows(&mut self) -> QueryRows<'_> {
+        QueryRows {
+            width: self.names.len(),
+            rows: self.stmt.raw_query(),
+        }
+    }
+    /// Load every row.
+    pub fn into_owned(mut self) -> Result<OwnedRows> {
+        let rows = self.rows().collect::<Result<_>>()?;
+        Ok(OwnedRows {
+            columns: self.names,
+            rows,
+        })
+    }
+    /// Run a statement returning no rows, such as an `INSERT`, returning the number of
+    /// changed rows.
+    pub fn execute(mut self) -> Result<usize> {
+        Ok(self.stmt.raw_execute()?)
+    }
+}
+
+impl<'a, 'conn> IntoIterator for &'a mut QueryResult<'conn> {
+    type Item = Result<Vec<Value>>;
+    type IntoIter = QueryRows<'a>;
+    fn into_iter(self) -> QueryRows<'a> {
+        self.rows()
+    }
+}
+
+/// Iterator over the rows of a [`QueryResult`].
+pub struct QueryRows<'stmt> {
+    rows: rusqlite::Rows<'stmt>,
+    width: usize,
+}
+
+impl Iterator for QueryRows<'_> {
+    type Item = Result<Vec<Value>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.rows.next() {
+            Ok(Some(row)) => Some(row_values(row, self.width).map_err(Into::into)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err.into())),
+        }
+    }
+}
+
+fn row_values(row: &rusqlite::Row, width: usize) -> rusqlite::Result<Vec<Value>> {
+    (0..width).map(|idx| row.get::<_, Value>(idx)).collect()
+}
+
+/// Database, table and column each result column of `sql` comes from. rusqlite doesn't
+/// expose them, so the statement is prepared once more through the C API to read them,
+/// which is why [`QueryResult::columns`] only does it on demand.
+fn column_origins(conn: &Connection, sql: &str) -> Vec<[Option<String>; 3]> {
+    let text = |ptr: *const c_char| {
+        (!ptr.is_null()).then(|| {
+            unsafe { CStr::from_ptr(ptr) }
+                .to_string_lossy()
+                .into_owned()
+        })
+    };
+    let Ok(len) = c_int::try_from(sql.len()) else {
+        return vec![];
+    };
+    // SAFETY: the statement is prepared on the connection's own handle, only read while
+    // it's alive and finalized before returning.
+    unsafe {
+        let mut stmt = std::ptr::null_mut();
+        let rc = ffi::sqlite3_prepare_v2(
+            conn.handle(),
+            sql.as_ptr().cast(),
+            len,
+            &mut stmt,
+            std::ptr::null_mut(),
+        );
+        if rc != ffi::SQLITE_OK || stmt.is_null() {
+            return vec![];
+        }
+        let origins = (0..ffi::sqlite3_column_count(stmt))
+            .map(|idx| {
+                [
+                    text(ffi::sqlite3_column_database_name(stmt, idx)),
+                    text(ffi::sqlite3_column_table_name(stmt, idx)),
+                    text(ffi::sqlite3_column_origin_name(stmt, idx)),
+                ]
+            })
+            .collect();
+        ffi::sqlite3_finalize(stmt);
+        origins
+    }
+}
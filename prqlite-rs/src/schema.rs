@@ -0,0 +1,239 @@
+use crate::{Prqlite, Result};
+
+/// Tables and views of every database attached to a connection, as returned by
+/// [`Prqlite::schema`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schema {
+    pub databases: Vec<Database>,
+}
+
+/// A database attached to the connection: `main`, `temp` or an `ATTACH`ed one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Database {
+    pub name: String,
+    /// Path of the database file, empty for in-memory and temporary databases.
+    pub file: String,
+    pub tables: Vec<Table>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableKind {
+    Table,
+    View,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    pub name: String,
+    pub kind: TableKind,
+    /// The `CREATE` statement of the table or view.
+    pub sql: Option<String>,
+    pub columns: Vec<Column>,
+    pub foreign_keys: Vec<ForeignKey>,
+    pub indexes: Vec<Index>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    /// Declared type, empty when the column was declared without one.
+    pub decl_type: String,
+    pub not_null: bool,
+    pub default: Option<String>,
+    /// 1-based position of the column in the primary key, `0` if not part of it.
+    pub primary_key: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKey {
+    /// Referenced table.
+    pub table: String,
+    /// Pairs of (local column, referenced column). The referenced column is `None` when
+    /// the key references the primary key implicitly.
+    pub columns: Vec<(String, Option<String>)>,
+    pub on_update: String,
+    pub on_delete: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Index {
+    pub name: String,
+    pub unique: bool,
+    /// `c` for `CREATE INDEX`, `u` for a UNIQUE constraint, `pk` for a PRIMARY KEY.
+    pub origin: String,
+    pub partial: bool,
+    pub columns: Vec<String>,
+}
+
+impl Schema {
+    pub fn database(&self, name: &str) -> Option<&Database> {
+        self.databases.iter().find(|db| db.name == name)
+    }
+    /// Find a table or view by name, looking in `main` first like SQLite does.
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables()
+            .find(|(_, table)| table.name.eq_ignore_ascii_case(name))
+            .map(|(_, t)| t)
+    }
+    /// Every table and view along with the name of the database holding it.
+    pub fn tables(&self) -> impl Iterator<Item = (&str, &Table)> {
+        let main = self.databases.iter().filter(|db| db.name == "main");
+        let others = self.databases.iter().filter(|db| db.name != "main");
+        main.chain(others)
+            .flat_map(|db| db.tables.iter().map(move |t| (db.name.as_str(), t)))
+    }
+}
+
+impl Table {
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns
+            .iter()
+            .find(|col| col.name.eq_ignore_ascii_case(name))
+    }
+    /// Columns of the primary key, in key order.
+    pub fn primary_key(&self) -> Vec<&Column> {
+        let mut pk: Vec<&Column> = self.columns.iter().filter(|c| c.primary_key > 0).collect();
+        pk.sort_by_key(|c| c.primary_key);
+        pk
+    }
+}
+
+impl Prqlite {
+    /// Read the schema of every attached database from `sqlite_schema` and the
+    /// `pragma_table_info`, `pragma_foreign_key_list` and `pragma_index_list` functions.
+    pub fn schema(&self) -> Result<Schema> {
+        let _timeout = self.start_timeout();
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name, file FROM pragma_database_list ORDER BY seq")?;
+        let databases = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut out = vec![];
+        for (name, file) in databases {
+            out.push(Database {
+                tables: self.schema_tables(&name)?,
+                file: file.unwrap_or_default(),
+                name,
+            });
+        }
+        Ok(Schema { databases: out })
+    }
+
+    fn schema_tables(&self, database: &str) -> Result<Vec<Table>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT type, name, sql FROM {}.sqlite_schema \
+             WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite_%' ORDER BY name",
+            quote_identifier(database)
+        ))?;
+        let tables = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut out = vec![];
+        for (kind, name, sql) in tables {
+            out.push(Table {
+                kind: match kind.as_str() {
+                    "view" => TableKind::View,
+                    _ => TableKind::Table,
+                },
+                columns: self.schema_columns(database, &name)?,
+                foreign_keys: self.schema_foreign_keys(database, &name)?,
+                indexes: self.schema_indexes(database, &name)?,
+                name,
+                sql,
+            });
+        }
+        Ok(out)
+    }
+
+    fn schema_columns(&self, database: &str, table: &str) -> Result<Vec<Column>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info(?1, ?2) ORDER BY cid",
+        )?;
+        let columns = stmt
+            .query_map([table, database], |row| {
+                Ok(Column {
+                    name: row.get(0)?,
+                    decl_type: row.get(1)?,
+                    not_null: row.get(2)?,
+                    default: row.get(3)?,
+                    primary_key: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(columns)
+    }
+
+    fn schema_foreign_keys(&self, database: &str, table: &str) -> Result<Vec<ForeignKey>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT id, \"table\", \"from\", \"to\", on_update, on_delete \
+             FROM pragma_foreign_key_list(?1, ?2) ORDER BY id, seq",
+        )?;
+        let mut rows = stmt.query([table, database])?;
+
+        let mut out: Vec<(i64, ForeignKey)> = vec![];
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            let column = (row.get::<_, String>(2)?, row.get::<_, Option<String>>(3)?);
+            match out.last_mut() {
+                Some((last, fk)) if *last == id => fk.columns.push(column),
+                _ => out.push((
+                    id,
+                    ForeignKey {
+                        table: row.get(1)?,
+                        columns: vec![column],
+                        on_update: row.get(4)?,
+                        on_delete: row.get(5)?,
+                    },
+                )),
+            }
+        }
+        Ok(out.into_iter().map(|(_, fk)| fk).collect())
+    }
+
+    fn schema_indexes(&self, database: &str, table: &str) -> Result<Vec<Index>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT name, \"unique\", origin, partial FROM pragma_index_list(?1, ?2) ORDER BY name",
+        )?;
+        let indexes = stmt
+            .query_map([table, database], |row| {
+                Ok(Index {
+                    name: row.get(0)?,
+                    unique: row.get(1)?,
+                    origin: row.get(2)?,
+                    partial: row.get(3)?,
+                    columns: vec![],
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT name FROM pragma_index_info(?1, ?2) ORDER BY seqno")?;
+        let mut out = vec![];
+        for mut index in indexes {
+            index.columns = stmt
+                .query_map([index.name.as_str(), database], |row| {
+                    Ok(row.get::<_, Option<String>>(0)?.unwrap_or_default())
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            out.push(index);
+        }
+        Ok(out)
+    }
+}
+
+/// Quote an SQL identifier with double quotes.
+pub(crate) fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
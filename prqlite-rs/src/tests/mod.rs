@@ -2,12 +2,8 @@ mod test_utils;
 use test_utils::*;
 
 use super::*;
-use rand::{distributions::Alphanumeric, Rng};
+use prql_compiler::{sql::Dialect, Target};
 use rusqlite::types::Value;
-use std::{
-    fs::{remove_file, File},
-    path::Path,
-};
 #[test]
 fn test_execute() {
     let db_path = "test.db";
@@ -63,3 +59,1348 @@ fn test_execute_with_sql() {
         idx += 1;
     }
 }
+
+#[test]
+fn test_execute_with_params() {
+    let db_path = "test_params.db";
+    let fake_data = generate_fake_data(5);
+
+    reset_database(db_path, fake_data.clone());
+
+    let conn = Prqlite::open(db_path).unwrap();
+    let (id, name, address) = fake_data.get(2).unwrap();
+
+    let mut stmt = conn
+        .execute_with_params(
+            "from persons | filter Name == $2 && ID == $1 | select [Address]",
+            params![id, name],
+        )
+        .unwrap();
+    let mut rows = stmt.raw_query();
+    let row = rows.next().unwrap().unwrap();
+    assert_eq!(
+        row.get::<_, Value>(0).unwrap(),
+        Value::from(address.to_owned())
+    );
+
+    let mut stmt = conn
+        .execute_with_params(
+            "from persons | filter Name == $name | select [ID]",
+            named_params! {"name": "'; DROP TABLE persons; --"},
+        )
+        .unwrap();
+    assert!(stmt.raw_query().next().unwrap().is_none());
+    assert!(conn.execute("from persons").is_ok());
+
+    assert!(conn
+        .execute_with_params(
+            "from persons | filter ID == $id",
+            named_params! {"missing": 1}
+        )
+        .is_err());
+
+    let missing = conn.stream_with_params(
+        "from persons | filter ID == $id || Name == $name",
+        named_params! {"id": id},
+    );
+    assert!(matches!(missing, Err(PrqliteError::Parameter(_))));
+
+    let missing =
+        conn.execute_with_params("from persons | filter Name == $2 && ID == $1", params![id]);
+    assert!(matches!(missing, Err(PrqliteError::Parameter(_))));
+    let extra = conn.execute_with_params("from persons | filter ID == $1", params![id, name]);
+    assert!(matches!(extra, Err(PrqliteError::Parameter(_))));
+}
+
+#[test]
+fn test_bind_placeholders() {
+    assert_eq!(
+        params::bind_placeholders("SELECT * FROM t WHERE a = $1 AND b = $name AND c = '$2'"),
+        "SELECT * FROM t WHERE a = ?1 AND b = $name AND c = '$2'"
+    );
+}
+
+#[derive(Debug, PartialEq)]
+struct Person {
+    id: i32,
+    name: String,
+    address: String,
+}
+impl_from_row!(Person { id, name, address = "Address" });
+
+#[test]
+fn test_query_as() {
+    let db_path = "test_query_as.db";
+    let fake_data = generate_fake_data(5);
+
+    reset_database(db_path, fake_data.clone());
+
+    let conn = Prqlite::open(db_path).unwrap();
+    let persons: Vec<Person> = conn
+        .query_as("from persons | select [Address, Name, ID]")
+        .unwrap();
+
+    assert_eq!(persons.len(), fake_data.len());
+    for (person, (id, name, address)) in persons.iter().zip(fake_data) {
+        assert_eq!(person, &Person { id, name, address });
+    }
+
+    let err = conn
+        .query_as::<Person>("from persons | select [ID, Name]")
+        .unwrap_err();
+    assert!(err.to_string().contains("'Address' is missing"));
+
+    let err = conn
+        .query_as::<Person>("from persons | select [ID = Name, Name, Address]")
+        .unwrap_err();
+    assert!(err.to_string().contains("column 'id' cannot be converted"));
+}
+
+#[test]
+fn test_errors() {
+    let conn = Prqlite::open(":memory:").unwrap();
+
+    match conn.execute("from persons | select [") {
+        Err(PrqliteError::Compile(messages)) => {
+            assert!(messages.inner[0].location.is_some());
+        }
+        other => panic!("expected compile error, got {:?}", other.err()),
+    }
+
+    match conn.execute("from missing_table") {
+        Err(err @ PrqliteError::Sqlite { .. }) => {
+            assert_eq!(err.extended_code(), Some(1));
+        }
+        other => panic!("expected SQLite error, got {:?}", other.err()),
+    }
+
+    assert!(matches!(
+        Prqlite::open("/nonexistent/dir/test.db"),
+        Err(PrqliteError::Open { .. })
+    ));
+}
+
+#[test]
+fn test_compile_options() {
+    let conn = Prqlite::open(":memory:").unwrap();
+    assert!(!conn.compile_options().format);
+    assert!(!conn.compile_options().signature_comment);
+    assert_eq!(
+        conn.compile("from persons | take 5").unwrap(),
+        "SELECT * FROM persons LIMIT 5"
+    );
+
+    let conn = Prqlite::builder()
+        .path(":memory:")
+        .format(true)
+        .signature_comment(true)
+        .target(Target::Sql(Some(Dialect::MsSql)))
+        .open()
+        .unwrap();
+    assert!(conn.compile_options().format);
+
+    let sql = conn.compile("from persons | take 5").unwrap();
+    assert!(sql.contains("TOP (5)"));
+    assert!(sql.contains("-- Generated by PRQL compiler"));
+
+    conn.execute_batch_with_sql("CREATE TABLE persons (id integer)")
+        .unwrap();
+    assert!(conn.execute("from persons | take 5").is_ok());
+}
+
+#[test]
+fn test_split_queries() {
+    let script = r#"# people named with a semicolon
+from persons
+filter Name == "a;b"   # a comment; with a semicolon
+select [ID]
+
+let adults = (
+  from persons
+
+  filter ID > 1
+)
+
+from adults; from persons | derive x = s"'; '"
+"#;
+    let queries = split_queries(script);
+    assert_eq!(queries.len(), 3);
+
+    assert_eq!(
+        queries[0].source,
+        "from persons\nfilter Name == \"a;b\"   # a comment; with a semicolon\nselect [ID]"
+    );
+    assert_eq!((queries[0].start_line, queries[0].end_line), (2, 4));
+
+    assert!(queries[1].source.starts_with("let adults"));
+    assert!(queries[1].source.ends_with("from adults"));
+    assert_eq!((queries[1].start_line, queries[1].end_line), (6, 12));
+
+    assert_eq!(queries[2].source, "from persons | derive x = s\"'; '\"");
+    assert_eq!((queries[2].start_line, queries[2].end_line), (12, 12));
+}
+
+#[test]
+fn test_execute_batch() {
+    let db_path = "test_execute_batch.db";
+    let fake_data = generate_fake_data(5);
+
+    reset_database(db_path, fake_data.clone());
+
+    let conn = Prqlite::open(db_path).unwrap();
+    let results = conn
+        .execute_batch("from persons | select [ID]\n\nfrom persons | take 2")
+        .unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].columns, vec!["ID"]);
+    assert_eq!(results[0].rows.len(), 5);
+    assert_eq!(results[1].rows.len(), 2);
+    assert_eq!(results[1].rows[0][0], Value::from(fake_data[0].0));
+
+    match conn.execute_batch("from persons\n\n\nfrom missing_table\nselect [a]") {
+        Err(PrqliteError::Batch {
+            index,
+            start_line,
+            end_line,
+            ..
+        }) => assert_eq!((index, start_line, end_line), (1, 4, 5)),
+        other => panic!("expected batch error, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_compile_cache() {
+    let conn = Prqlite::builder().cache_capacity(2).open().unwrap();
+    conn.execute_batch_with_sql("CREATE TABLE persons (id integer)")
+        .unwrap();
+
+    for _ in 0..3 {
+        conn.execute("from persons").unwrap();
+    }
+    assert_eq!(
+        conn.cache_stats(),
+        CacheStats {
+            hits: 2,
+            misses: 1,
+            len: 1,
+            capacity: 2
+        }
+    );
+
+    conn.execute("from persons | take 1").unwrap();
+    conn.execute("from persons | take 2").unwrap();
+    conn.execute("from persons").unwrap();
+    let stats = conn.cache_stats();
+    assert_eq!((stats.hits, stats.misses, stats.len), (2, 4, 2));
+
+    assert!(conn.execute("from persons | select [").is_err());
+    assert_eq!(conn.cache_stats().len, 2);
+
+    conn.clear_cache();
+    assert_eq!(conn.cache_stats().len, 0);
+
+    let conn = Prqlite::builder().cache_capacity(0).open().unwrap();
+    conn.execute_batch_with_sql("CREATE TABLE persons (id integer)")
+        .unwrap();
+    conn.execute("from persons").unwrap();
+    conn.execute("from persons").unwrap();
+    assert_eq!(conn.cache_stats().hits, 0);
+}
+
+#[test]
+fn test_transaction() {
+    let conn = Prqlite::open(":memory:").unwrap();
+    conn.execute_batch_with_sql("CREATE TABLE persons (id integer)")
+        .unwrap();
+    let count = |conn: &Prqlite| {
+        conn.execute("from persons")
+            .unwrap()
+            .query([])
+            .unwrap()
+            .mapped(|_| Ok(()))
+            .count()
+    };
+
+    let tx = conn.transaction().unwrap();
+    tx.execute_with_sql("INSERT INTO persons VALUES (1)")
+        .unwrap()
+        .execute([])
+        .unwrap();
+    assert_eq!(count(&tx), 1);
+    tx.rollback().unwrap();
+    assert_eq!(count(&conn), 0);
+
+    {
+        let tx = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .unwrap();
+        tx.execute_batch_with_sql("INSERT INTO persons VALUES (1)")
+            .unwrap();
+    }
+    assert_eq!(count(&conn), 0);
+
+    let mut tx = conn.transaction().unwrap();
+    tx.execute_batch_with_sql("INSERT INTO persons VALUES (1)")
+        .unwrap();
+    {
+        let mut sp = tx.savepoint().unwrap();
+        assert!(sp.is_savepoint());
+        sp.execute_batch_with_sql("INSERT INTO persons VALUES (2)")
+            .unwrap();
+        let nested = sp.savepoint().unwrap();
+        nested
+            .execute_batch_with_sql("INSERT INTO persons VALUES (3)")
+            .unwrap();
+        nested.commit().unwrap();
+        assert_eq!(count(&sp), 3);
+        sp.rollback().unwrap();
+    }
+    {
+        let sp = tx.savepoint().unwrap();
+        sp.execute_batch_with_sql("INSERT INTO persons VALUES (4)")
+            .unwrap();
+        sp.commit().unwrap();
+    }
+    tx.commit().unwrap();
+    assert_eq!(count(&conn), 2);
+
+    // A failed COMMIT leaves the transaction open until the guard rolls it back.
+    conn.execute_batch_with_sql(
+        "PRAGMA foreign_keys = ON;
+        CREATE TABLE parents (id integer PRIMARY KEY);
+        CREATE TABLE children (parent integer REFERENCES parents DEFERRABLE INITIALLY DEFERRED);",
+    )
+    .unwrap();
+    let tx = conn.transaction().unwrap();
+    tx.execute_batch_with_sql("INSERT INTO children VALUES (1)")
+        .unwrap();
+    assert!(tx.commit().is_err());
+    assert!(conn.conn.is_autocommit());
+    assert!(conn
+        .execute("from children")
+        .unwrap()
+        .query([])
+        .unwrap()
+        .next()
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_schema() {
+    let conn = Prqlite::open(":memory:").unwrap();
+    conn.execute_batch_with_sql(
+        r#"CREATE TABLE persons (
+                ID integer PRIMARY KEY,
+                Name varchar(255) NOT NULL,
+                Address varchar(255) DEFAULT 'unknown'
+            );
+            CREATE TABLE orders (
+                id integer,
+                person_id integer REFERENCES persons (ID) ON DELETE CASCADE,
+                total real,
+                PRIMARY KEY (id, person_id)
+            );
+            CREATE UNIQUE INDEX orders_total ON orders (total, person_id);
+            CREATE VIEW names AS SELECT Name FROM persons;
+            ATTACH DATABASE ':memory:' AS other;
+            CREATE TABLE other.items (sku text);"#,
+    )
+    .unwrap();
+
+    let schema = conn.schema().unwrap();
+    let main = schema.database("main").unwrap();
+    assert_eq!(
+        main.tables
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["names", "orders", "persons"]
+    );
+
+    let persons = schema.table("persons").unwrap();
+    assert_eq!(persons.kind, TableKind::Table);
+    assert_eq!(
+        persons.column("name").unwrap(),
+        &Column {
+            name: "Name".to_owned(),
+            decl_type: "varchar(255)".to_owned(),
+            not_null: true,
+            default: None,
+            primary_key: 0,
+        }
+    );
+    assert_eq!(
+        persons.column("Address").unwrap().default.as_deref(),
+        Some("'unknown'")
+    );
+
+    let orders = schema.table("orders").unwrap();
+    assert_eq!(
+        orders
+            .primary_key()
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["id", "person_id"]
+    );
+    assert_eq!(
+        orders.foreign_keys,
+        vec![ForeignKey {
+            table: "persons".to_owned(),
+            columns: vec![("person_id".to_owned(), Some("ID".to_owned()))],
+            on_update: "NO ACTION".to_owned(),
+            on_delete: "CASCADE".to_owned(),
+        }]
+    );
+    let index = orders
+        .indexes
+        .iter()
+        .find(|i| i.name == "orders_total")
+        .unwrap();
+    assert!(index.unique);
+    assert_eq!(index.columns, vec!["total", "person_id"]);
+
+    assert_eq!(schema.table("names").unwrap().kind, TableKind::View);
+    assert_eq!(schema.database("other").unwrap().tables[0].name, "items");
+    assert!(schema.table("items").is_some());
+}
+
+#[test]
+fn test_declare_schema() {
+    let conn = Prqlite::builder().declare_schema(true).open().unwrap();
+    conn.execute_batch_with_sql(
+        r#"CREATE TABLE persons (ID integer, Name text, "home address" text);
+            CREATE TABLE "count" (n integer);"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        conn.prelude().unwrap().unwrap(),
+        "let persons = (from `main.persons` | select [ID, Name, `home address`])\n"
+    );
+    assert!(conn
+        .execute("from persons | select [Name, `home address`]")
+        .is_ok());
+
+    match conn.execute("from persons\nselect [Name, Adress]") {
+        Err(PrqliteError::Compile(messages)) => {
+            let message = &messages.inner[0];
+            assert_eq!(message.reason, "Unknown name `Adress`");
+            let location = message.location.as_ref().unwrap();
+            assert_eq!((location.start, location.end), ((1, 14), (1, 20)));
+            assert!(message
+                .display
+                .as_ref()
+                .unwrap()
+                .contains("select [Name, Adress]"));
+        }
+        other => panic!("expected compile error, got {:?}", other.err()),
+    }
+
+    // The declarations go after the header of a query, errors pointing at the query.
+    assert!(conn
+        .execute("prql target:sql.sqlite\n\nfrom persons | select [Name]")
+        .is_ok());
+    match conn.execute("# header\nprql target:sql.sqlite\nfrom persons\nselect [Adress]") {
+        Err(PrqliteError::Compile(messages)) => {
+            let location = messages.inner[0].location.as_ref().unwrap();
+            assert_eq!((location.start, location.end), ((3, 8), (3, 14)));
+        }
+        other => panic!("expected compile error, got {:?}", other.err()),
+    }
+
+    conn.execute_batch_with_sql("ALTER TABLE persons ADD COLUMN Adress text")
+        .unwrap();
+    assert!(conn.execute("from persons\nselect [Name, Adress]").is_ok());
+}
+
+#[test]
+fn test_error_origin() {
+    let conn = Prqlite::open(":memory:").unwrap();
+    conn.execute_batch_with_sql("CREATE TABLE persons (id integer, name text)")
+        .unwrap();
+
+    let err = conn
+        .execute("from persons\nfilter id > 1\nselect [name, adress]")
+        .err()
+        .unwrap();
+    let origin = err.origin().unwrap();
+    assert!(origin.sql.starts_with("SELECT"));
+    assert_eq!(origin.sql_fragment.as_deref(), Some("adress"));
+
+    let location = origin.prql_location.as_ref().unwrap();
+    assert_eq!((location.line, location.column, location.len), (2, 14, 6));
+    assert_eq!(
+        location.caret(),
+        "3 | select [name, adress]\n                  ^^^^^^"
+    );
+    assert!(err.to_string().ends_with("(PRQL line 3, column 15)"));
+
+    let err = conn
+        .execute("from persons | derive x = s\"missing_fn({name})\"")
+        .err()
+        .unwrap();
+    let location = err.origin().unwrap().prql_location.as_ref().unwrap();
+    assert_eq!((location.line, location.column), (0, 28));
+
+    let err = conn.execute("from nowhere").err().unwrap();
+    assert_eq!(
+        err.origin().unwrap().sql_fragment.as_deref(),
+        Some("nowhere")
+    );
+
+    // A token found twice in the PRQL source can't be located.
+    let err = conn
+        .execute("from persons\nfilter adress != null\nselect [name, adress]")
+        .err()
+        .unwrap();
+    let origin = err.origin().unwrap();
+    assert_eq!(origin.sql_fragment.as_deref(), Some("adress"));
+    assert_eq!(origin.prql_location, None);
+}
+
+#[test]
+fn test_async_prqlite() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let conn = AsyncPrqlite::open(":memory:").await.unwrap();
+        conn.execute_batch_with_sql("CREATE TABLE persons (id integer, name text)")
+            .await
+            .unwrap();
+        let inserted = conn
+            .execute_with_sql("INSERT INTO persons VALUES (1, 'a'), (2, 'b')")
+            .await
+            .unwrap();
+        assert_eq!(inserted, 2);
+
+        let clone = conn.clone();
+        let rows = clone
+            .query("from persons | filter id > 1 | select [name]")
+            .await
+            .unwrap();
+        assert_eq!(rows.columns, ["name"]);
+        assert_eq!(rows.rows, [[Value::Text("b".to_owned())]]);
+
+        let err = conn
+            .transaction(|tx| {
+                tx.execute_batch_with_sql("INSERT INTO persons VALUES (3, 'c')")?;
+                tx.execute_batch_with_sql("INSERT INTO nowhere VALUES (1)")
+            })
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, PrqliteError::Sqlite { .. }));
+        let count = conn
+            .call(|prqlite| Ok(prqlite.query("from persons")?.len()))
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(conn.execute("from persons").await.unwrap(), 2);
+
+        assert!(conn.query("from persons | select [adress]").await.is_err());
+        assert!(AsyncPrqlite::open("/nowhere/test.db").await.is_err());
+    });
+}
+
+#[test]
+fn test_pool() {
+    let db_path = "test_pool.db";
+    let _ = std::fs::remove_file(db_path);
+    assert!(matches!(
+        PrqlitePool::open(":memory:"),
+        Err(PrqliteError::Pool(_))
+    ));
+
+    let connections = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = connections.clone();
+    let pool = PrqlitePool::builder(db_path)
+        .readers(2)
+        .on_connect(move |prqlite| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            prqlite.execute_batch_with_sql("PRAGMA foreign_keys = ON")
+        })
+        .open()
+        .unwrap();
+    assert_eq!(connections.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+    {
+        let writer = pool.writer();
+        assert!(writer.is_writer());
+        assert!(pool.try_writer().is_none());
+        writer
+            .execute_batch_with_sql(
+                "CREATE TABLE persons (id integer, name text);
+                 INSERT INTO persons VALUES (1, 'a'), (2, 'b');",
+            )
+            .unwrap();
+    }
+
+    let reader = pool.reader();
+    assert!(reader
+        .execute_batch_with_sql("INSERT INTO persons VALUES (3, 'c')")
+        .is_err());
+    let other = pool.try_reader().unwrap();
+    assert!(pool.try_reader().is_none());
+    assert_eq!(pool.idle_readers(), 0);
+    drop((reader, other));
+    assert_eq!(pool.idle_readers(), pool.size());
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let pool = pool.clone();
+            std::thread::spawn(move || pool.reader().query("from persons").unwrap().len())
+        })
+        .collect();
+    for handle in handles {
+        assert_eq!(handle.join().unwrap(), 2);
+    }
+}
+
+#[test]
+fn test_open_options() {
+    let db_path = "test_open_options.db";
+    let _ = std::fs::remove_file(db_path);
+
+    let err = Prqlite::builder()
+        .path(db_path)
+        .create(false)
+        .open()
+        .err()
+        .unwrap();
+    assert!(matches!(err, PrqliteError::Open { .. }));
+    assert!(!std::path::Path::new(db_path).exists());
+    assert!(Prqlite::builder()
+        .path(db_path)
+        .read_only(true)
+        .open()
+        .is_err());
+
+    Prqlite::open(db_path)
+        .unwrap()
+        .execute_batch_with_sql("CREATE TABLE persons (id integer)")
+        .unwrap();
+
+    let read_only = Prqlite::builder()
+        .path(db_path)
+        .read_only(true)
+        .busy_timeout(std::time::Duration::from_millis(100))
+        .open()
+        .unwrap();
+    assert!(read_only.query("from persons").unwrap().is_empty());
+    let err = read_only
+        .execute_batch_with_sql("INSERT INTO persons VALUES (1)")
+        .err()
+        .unwrap();
+    assert_eq!(err.extended_code(), Some(8));
+
+    let uri = Prqlite::open(&format!("file:{db_path}?mode=ro")).unwrap();
+    assert!(uri
+        .execute_batch_with_sql("INSERT INTO persons VALUES (1)")
+        .is_err());
+
+    let first = Prqlite::builder()
+        .shared_memory("test_open_options")
+        .open()
+        .unwrap();
+    first
+        .execute_batch_with_sql("CREATE TABLE shared (id integer); INSERT INTO shared VALUES (1)")
+        .unwrap();
+    let second = Prqlite::builder()
+        .shared_memory("test_open_options")
+        .open()
+        .unwrap();
+    assert_eq!(second.query("from shared").unwrap().len(), 1);
+}
+
+#[test]
+fn test_register_functions() {
+    struct Product;
+    impl Aggregate<f64, Option<f64>> for Product {
+        fn init(&self, _: &mut Context<'_>) -> rusqlite::Result<f64> {
+            Ok(1.0)
+        }
+        fn step(&self, ctx: &mut Context<'_>, acc: &mut f64) -> rusqlite::Result<()> {
+            *acc *= ctx.get::<f64>(0)?;
+            Ok(())
+        }
+        fn finalize(&self, _: &mut Context<'_>, acc: Option<f64>) -> rusqlite::Result<Option<f64>> {
+            Ok(acc)
+        }
+    }
+
+    let conn = Prqlite::open(":memory:").unwrap();
+    conn.execute_batch_with_sql(
+        "CREATE TABLE prices (grp text, amount real);
+         INSERT INTO prices VALUES ('a', 1.234), ('a', 2.0), ('b', 3.456);",
+    )
+    .unwrap();
+
+    conn.register_scalar(
+        "money_round",
+        1,
+        FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| Ok((ctx.get::<f64>(0)? * 100.0).round() / 100.0),
+    )
+    .unwrap();
+    conn.register_aggregate("product", 1, FunctionFlags::SQLITE_DETERMINISTIC, Product)
+        .unwrap();
+    conn.register_scalar("concat_all", -1, FunctionFlags::empty(), |ctx| {
+        (0..ctx.len())
+            .map(|idx| ctx.get::<String>(idx))
+            .collect::<rusqlite::Result<String>>()
+    })
+    .unwrap();
+    assert_eq!(
+        conn.prelude().unwrap().unwrap(),
+        "func money_round arg0 -> s\"money_round({arg0})\"\n\
+         func product arg0 -> s\"product({arg0})\"\n"
+    );
+
+    let rows = conn
+        .query("from prices | derive [rounded = money_round amount] | select [rounded]")
+        .unwrap();
+    assert_eq!(
+        rows.rows,
+        [[Value::Real(1.23)], [Value::Real(2.0)], [Value::Real(3.46)]]
+    );
+
+    let rows = conn
+        .query("from prices | group grp (aggregate [p = product amount]) | sort grp")
+        .unwrap();
+    assert_eq!(rows.rows[0], [Value::Text("a".into()), Value::Real(2.468)]);
+
+    let rows = conn
+        .query("from prices | take 1 | select [s = s\"concat_all(grp, 'x', grp)\"]")
+        .unwrap();
+    assert_eq!(rows.rows, [[Value::Text("axa".into())]]);
+
+    // Declared after the header of a query.
+    let rows = conn
+        .query("prql target:sql.sqlite\nfrom prices | take 1 | select [r = money_round amount]")
+        .unwrap();
+    assert_eq!(rows.rows, [[Value::Real(1.23)]]);
+
+    // PRQL has no overloading: the last registered arity is declared, and the other one
+    // again once it is removed.
+    conn.register_scalar(
+        "money_round",
+        2,
+        FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let scale = 10f64.powi(ctx.get(1)?);
+            Ok((ctx.get::<f64>(0)? * scale).round() / scale)
+        },
+    )
+    .unwrap();
+    let rows = conn
+        .query("from prices | take 1 | select [rounded = money_round amount 1]")
+        .unwrap();
+    assert_eq!(rows.rows, [[Value::Real(1.2)]]);
+    conn.remove_function("money_round", 2).unwrap();
+    assert!(conn
+        .query("from prices | derive [rounded = money_round amount]")
+        .is_ok());
+
+    conn.remove_function("money_round", 1).unwrap();
+    assert!(conn.prelude().unwrap().unwrap().starts_with("func product"));
+    assert!(conn
+        .query("from prices | derive [rounded = money_round amount]")
+        .is_err());
+}
+
+#[test]
+fn test_stream() {
+    let conn = Prqlite::open(":memory:").unwrap();
+    conn.execute_batch_with_sql(
+        "CREATE TABLE persons (id integer, name varchar(20));
+         INSERT INTO persons VALUES (1, 'a'), (2, 'b'), (3, 'c');",
+    )
+    .unwrap();
+
+    let mut result = conn
+        .stream("from persons | derive [next = id + 1] | select [name, next]")
+        .unwrap();
+    assert_eq!(result.column_names(), ["name", "next"]);
+    let name = &result.columns()[0];
+    assert_eq!(name.decl_type.as_deref(), Some("varchar(20)"));
+    assert_eq!(name.table.as_deref(), Some("persons"));
+    assert_eq!(name.database.as_deref(), Some("main"));
+    assert_eq!(name.origin_name.as_deref(), Some("name"));
+    let next = &result.columns()[1];
+    assert_eq!((next.decl_type.as_ref(), next.table.as_ref()), (None, None));
+
+    let mut seen = 0;
+    for row in &mut result {
+        let row = row.unwrap();
+        seen += 1;
+        assert_eq!(row[1], Value::Integer(seen + 1));
+    }
+    assert_eq!(seen, 3);
+    let first_two = result.rows().take(2).collect::<Result<Vec<_>>>().unwrap();
+    assert_eq!(first_two.len(), 2);
+    assert_eq!(result.into_owned().unwrap().len(), 3);
+
+    let rows = conn
+        .stream_with_params(
+            "from persons | filter id > $1 | select [id]",
+            &[&1 as &dyn rusqlite::ToSql][..],
+        )
+        .unwrap()
+        .into_owned()
+        .unwrap();
+    assert_eq!(rows.rows, [[Value::Integer(2)], [Value::Integer(3)]]);
+
+    let changed = conn
+        .stream_with_sql("DELETE FROM persons WHERE id > 1")
+        .unwrap()
+        .execute()
+        .unwrap();
+    assert_eq!(changed, 2);
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn test_query_arrow() {
+    use arrow_array::{Array, Float64Array, Int64Array, RecordBatch, StringArray};
+    use arrow_schema::DataType;
+
+    let conn = Prqlite::open(":memory:").unwrap();
+    conn.execute_batch_with_sql(
+        "CREATE TABLE persons (id integer, name varchar(20), score real, data blob);
+         INSERT INTO persons VALUES (1, 'a', 1.5, NULL), (2, NULL, 2, x'00'), (3, 'c', NULL, NULL);",
+    )
+    .unwrap();
+
+    let batches = conn
+        .query_arrow(
+            "from persons | derive [half = id / 2, label = s\"name || '!'\"] | sort id",
+            2,
+        )
+        .unwrap()
+        .collect_batches()
+        .unwrap();
+    assert_eq!(
+        batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>(),
+        [2, 1]
+    );
+
+    let schema = batches[0].schema();
+    let types: Vec<_> = schema
+        .fields()
+        .iter()
+        .map(|f| f.data_type().clone())
+        .collect();
+    assert_eq!(
+        types,
+        [
+            DataType::Int64,
+            DataType::Utf8,
+            DataType::Float64,
+            DataType::Binary,
+            DataType::Int64,
+            DataType::Utf8
+        ]
+    );
+    let ids = batches[1]
+        .column(0)
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap();
+    assert_eq!(ids.value(0), 3);
+    let names = batches[0]
+        .column(1)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert!(names.is_null(1));
+    let scores = batches[0]
+        .column(2)
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .unwrap();
+    assert_eq!(scores.value(1), 2.0);
+
+    let empty = conn
+        .query_arrow("from persons | filter id > 10 | select [id]", 0)
+        .unwrap()
+        .collect_batches()
+        .unwrap();
+    assert_eq!(empty.len(), 1);
+    assert_eq!(empty[0].num_rows(), 0);
+
+    // Types are widened to hold values of other types found in later batches.
+    conn.execute_batch_with_sql(
+        "CREATE TABLE mixed (n integer, t, b, v);
+         INSERT INTO mixed VALUES (1, 1, 'a', NULL), (2.5, 'x', x'01', NULL);",
+    )
+    .unwrap();
+    let types = |batch: &RecordBatch| -> Vec<DataType> {
+        batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.data_type().clone())
+            .collect()
+    };
+    let batches = conn
+        .query_arrow("from mixed", 1)
+        .unwrap()
+        .collect_batches()
+        .unwrap();
+    assert_eq!(batches.len(), 2);
+    use DataType::{Binary, Float64, Int64, Utf8};
+    assert_eq!(types(&batches[0]), [Int64, Int64, Utf8, Utf8]);
+    assert_eq!(types(&batches[1]), [Float64, Utf8, Binary, Utf8]);
+
+    // Scanning the types first gives every batch the same schema.
+    let mut query = conn.query_arrow("from mixed", 1).unwrap();
+    query.scan_types(true);
+    let batches = query.collect_batches().unwrap();
+    assert_eq!(types(&batches[0]), [Float64, Utf8, Binary, Utf8]);
+    assert_eq!(types(&batches[1]), types(&batches[0]));
+    let t = batches[0]
+        .column(1)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(t.value(0), "1");
+
+    conn.execute_batch_with_sql("INSERT INTO persons VALUES ('four', 'd', NULL, NULL)")
+        .unwrap();
+    let batches = conn
+        .query_arrow("from persons", 0)
+        .unwrap()
+        .collect_batches()
+        .unwrap();
+    assert_eq!(batches[0].schema().field(0).data_type(), &DataType::Utf8);
+}
+
+#[test]
+fn test_dml_helpers() {
+    let conn = Prqlite::open(":memory:").unwrap();
+    conn.execute_batch_with_sql(
+        "CREATE TABLE orders (id integer primary key, customer text, total real);
+         INSERT INTO orders VALUES (1, 'a', 50), (2, 'b', 150), (3, 'a', 250);
+         CREATE TABLE big_orders (total real, id integer primary key);",
+    )
+    .unwrap();
+
+    let inserted = conn
+        .insert_into(
+            "big_orders",
+            "from orders | filter total > 100 | select [id, total]",
+        )
+        .unwrap();
+    assert_eq!(inserted, 2);
+    let rows = conn
+        .query("from big_orders | sort id | select [id, total]")
+        .unwrap();
+    assert_eq!(
+        rows.rows,
+        [
+            [Value::Integer(2), Value::Real(150.0)],
+            [Value::Integer(3), Value::Real(250.0)]
+        ]
+    );
+
+    assert!(conn
+        .insert_into(
+            "big_orders",
+            "from orders | filter id == 2 | select [id, total]"
+        )
+        .is_err());
+    let replaced = conn
+        .replace_into(
+            "big_orders",
+            "from orders | filter id == 2 | derive [total = total * 2] | select [id, total]",
+        )
+        .unwrap();
+    assert_eq!(replaced, 1);
+    let cte = "from orders | sort [-total] | take 1 | filter total > 0 | select [id, total]";
+    assert!(conn.compile(cte).unwrap().starts_with("WITH"));
+    assert_eq!(conn.replace_into("big_orders", cte).unwrap(), 1);
+
+    let created = conn
+        .create_table_as(
+            "customer_totals",
+            "from orders | group customer (aggregate [sum_total = sum total])",
+        )
+        .unwrap();
+    assert_eq!(created, 2);
+    conn.create_view(
+        "top_customer",
+        "from customer_totals | sort [-sum_total] | take 1",
+    )
+    .unwrap();
+    let rows = conn.query("from top_customer | select [customer]").unwrap();
+    assert_eq!(rows.rows, [[Value::Text("a".to_owned())]]);
+
+    let err = conn
+        .insert_into("nowhere", "from orders | select [id]")
+        .err()
+        .unwrap();
+    assert!(err.origin().is_some());
+}
+
+#[test]
+fn test_interrupt() {
+    let endless = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) \
+                   SELECT COUNT(*) FROM c";
+    let conn = Prqlite::builder()
+        .timeout(std::time::Duration::from_millis(100))
+        .open()
+        .unwrap();
+    conn.execute_batch_with_sql(
+        "CREATE TABLE persons (id integer); INSERT INTO persons VALUES (1)",
+    )
+    .unwrap();
+
+    let started = std::time::Instant::now();
+    let err = conn.execute_batch_with_sql(endless).err().unwrap();
+    assert!(err.is_interrupted());
+    assert!(matches!(err, PrqliteError::Interrupted { .. }));
+    assert_eq!(err.extended_code(), Some(9));
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+    std::thread::sleep(std::time::Duration::from_millis(150));
+    assert_eq!(conn.query("from persons").unwrap().len(), 1);
+    assert!(conn.schema().is_ok());
+
+    conn.set_timeout(None);
+    assert_eq!(conn.timeout(), None);
+    let handle = conn.interrupt_handle();
+    let interrupter = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        handle.interrupt();
+    });
+    let err = conn
+        .stream_with_sql(endless)
+        .unwrap()
+        .into_owned()
+        .err()
+        .unwrap();
+    interrupter.join().unwrap();
+    assert!(err.is_interrupted());
+    assert_eq!(conn.query("from persons").unwrap().len(), 1);
+
+    // The timeout counts from when a statement first runs, and stops when it's dropped.
+    conn.set_timeout(Some(std::time::Duration::from_millis(100)));
+    let count = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c WHERE x < 10000) \
+                 SELECT COUNT(*) FROM c";
+    conn.execute_batch_with_sql(&format!("CREATE TABLE numbers AS {count}"))
+        .unwrap();
+    let mut stmt = conn
+        .execute(&format!("from numbers | derive x = s\"({count})\""))
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(150));
+    let total: i64 = stmt.query_row([], |row| row.get(1)).unwrap();
+    assert_eq!(total, 10000);
+    drop(stmt);
+
+    let mut result = conn.stream_with_sql(count).unwrap();
+    assert!(result.rows().next().is_some());
+    std::thread::sleep(std::time::Duration::from_millis(150));
+    drop(result);
+    let total: i64 = conn.conn.query_row(count, [], |row| row.get(0)).unwrap();
+    assert_eq!(total, 10000);
+
+    // Other queries run while a result is streamed don't restart its clock.
+    let numbers = "WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c) SELECT x FROM c";
+    let started = std::time::Instant::now();
+    let mut result = conn.stream_with_sql(numbers).unwrap();
+    let err = result
+        .rows()
+        .enumerate()
+        .find_map(|(idx, row)| {
+            if idx % 10000 == 0 {
+                conn.query("from persons").ok()?;
+            }
+            row.err()
+        })
+        .unwrap();
+    assert!(err.is_interrupted());
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+}
+
+#[test]
+fn test_backup_restore() {
+    let backup_path = "test_backup.db";
+    let _ = std::fs::remove_file(backup_path);
+
+    let mut conn = Prqlite::builder().declare_schema(true).open().unwrap();
+    conn.execute_batch_with_sql(
+        "CREATE TABLE persons (id integer, name text);
+         WITH RECURSIVE c(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM c LIMIT 5000)
+         INSERT INTO persons SELECT x, 'name ' || x || hex(randomblob(200)) FROM c;",
+    )
+    .unwrap();
+
+    let mut remaining = vec![];
+    conn.backup_to(backup_path, |progress: BackupProgress| {
+        remaining.push(progress.remaining)
+    })
+    .unwrap();
+    assert!(remaining.len() > 1);
+    assert_eq!(remaining.last(), Some(&0));
+
+    let backup = Prqlite::open(backup_path).unwrap();
+    assert_eq!(backup.query("from persons").unwrap().len(), 5000);
+
+    conn.execute_batch_with_sql(
+        "DROP TABLE persons; CREATE TABLE persons (id integer, other text);",
+    )
+    .unwrap();
+    assert!(conn.execute("from persons | select [name]").is_err());
+    conn.restore_from(backup_path, |_| {}).unwrap();
+    assert_eq!(
+        conn.query("from persons | select [name]").unwrap().len(),
+        5000
+    );
+
+    assert!(matches!(
+        conn.restore_from("test_backup_missing.db", |_| {}),
+        Err(PrqliteError::Open { .. })
+    ));
+}
+
+#[test]
+fn test_import_csv() {
+    let conn = Prqlite::open(":memory:").unwrap();
+    let csv = "id,name,score,note\n\
+               1,alice,9.5,\n\
+               2,\"bob, jr\",7,late\n\
+               3,carol\n\
+               4,dave,8,\n";
+    let report = conn
+        .import_csv_from(csv.as_bytes(), "people", &CsvOptions::new())
+        .unwrap();
+    assert!(report.created);
+    assert_eq!(
+        report.columns,
+        [
+            ("id", "INTEGER"),
+            ("name", "TEXT"),
+            ("score", "REAL"),
+            ("note", "TEXT")
+        ]
+        .map(|(name, ty)| (name.to_owned(), ty.to_owned()))
+    );
+    assert_eq!(report.inserted, 3);
+    assert_eq!(report.rejected.len(), 1);
+    assert_eq!(report.rejected[0].line, 4);
+
+    let rows = conn
+        .query("from people | filter id == 2 | select [name, score, note]")
+        .unwrap();
+    assert_eq!(
+        rows.rows[0],
+        vec![
+            Value::Text("bob, jr".to_owned()),
+            Value::Real(7.0),
+            Value::Text("late".to_owned())
+        ]
+    );
+    let rows = conn.query("from people | filter note == null").unwrap();
+    assert_eq!(rows.len(), 2);
+
+    // Appended to the existing table by position, with a custom delimiter.
+    conn.execute_batch_with_sql("CREATE UNIQUE INDEX people_id ON people (id)")
+        .unwrap();
+    let mut options = CsvOptions::new();
+    options.delimiter(b';').has_header(false);
+    let report = conn
+        .import_csv_from("5;erin;6.5;\n1;again;1;\n".as_bytes(), "people", &options)
+        .unwrap();
+    assert!(!report.created);
+    assert_eq!(report.inserted, 1);
+    assert_eq!(report.rejected[0].line, 2);
+    assert_eq!(conn.query("from people").unwrap().len(), 4);
+
+    assert!(matches!(
+        conn.import_csv_from("id,unknown\n1,2\n".as_bytes(), "people", &CsvOptions::new()),
+        Err(PrqliteError::Import(_))
+    ));
+    assert_eq!(conn.query("from people").unwrap().len(), 4);
+    assert!(matches!(
+        conn.import_csv("test_import_missing.csv", "people", &CsvOptions::new()),
+        Err(PrqliteError::Import(_))
+    ));
+
+    // Inside the caller's transaction, which decides whether the import is kept.
+    let tx = conn.transaction().unwrap();
+    let report = tx
+        .import_csv_from(
+            "code,n\n02134,1\n10001,2\n".as_bytes(),
+            "zips",
+            &CsvOptions::new(),
+        )
+        .unwrap();
+    assert_eq!(report.inserted, 2);
+    assert_eq!(
+        report.columns,
+        [("code", "TEXT"), ("n", "INTEGER")].map(|(name, ty)| (name.to_owned(), ty.to_owned()))
+    );
+    assert_eq!(
+        tx.query("from zips | select [code]").unwrap().rows[0],
+        vec![Value::Text("02134".to_owned())]
+    );
+    tx.rollback().unwrap();
+    assert!(conn.query("from zips").is_err());
+}
+
+#[test]
+fn test_export() {
+    let conn = Prqlite::open(":memory:").unwrap();
+    conn.execute_batch_with_sql(
+        "CREATE TABLE items (id integer, name text, price real, data blob);
+         INSERT INTO items VALUES (1, 'plain', 1.5, x'666f6f'),
+             (2, 'a, \"quoted\" | <b>', NULL, NULL);",
+    )
+    .unwrap();
+    let export = |format, blobs| {
+        let mut options = ExportOptions::new(format);
+        options.blob_encoding(blobs);
+        let mut out = vec![];
+        let rows = conn.export("from items", &options, &mut out).unwrap();
+        assert_eq!(rows, 2);
+        String::from_utf8(out).unwrap()
+    };
+
+    assert_eq!(
+        export(ExportFormat::Csv, BlobEncoding::Base64),
+        "id,name,price,data\n1,plain,1.5,Zm9v\n2,\"a, \"\"quoted\"\" | <b>\",,\n"
+    );
+    assert_eq!(
+        export(ExportFormat::Json, BlobEncoding::Hex),
+        "[\n{\"id\":1,\"name\":\"plain\",\"price\":1.5,\"data\":\"666f6f\"},\n\
+         {\"id\":2,\"name\":\"a, \\\"quoted\\\" | <b>\",\"price\":null,\"data\":null}\n]\n"
+    );
+    assert_eq!(
+        export(ExportFormat::Ndjson, BlobEncoding::Hex)
+            .lines()
+            .count(),
+        2
+    );
+    assert_eq!(
+        export(ExportFormat::Markdown, BlobEncoding::Base64),
+        "| id | name | price | data |\n| --- | --- | --- | --- |\n\
+         | 1 | plain | 1.5 | Zm9v |\n| 2 | a, \"quoted\" \\| <b> |  |  |\n"
+    );
+    let html = export(ExportFormat::Html, BlobEncoding::Base64);
+    assert!(html.contains("<th>id</th><th>name</th>"));
+    assert!(html.contains("<td>a, &quot;quoted&quot; | &lt;b&gt;</td><td></td>"));
+
+    let mut out = vec![];
+    let options = ExportOptions::new(ExportFormat::Json);
+    conn.export("from items | filter id > 5", &options, &mut out)
+        .unwrap();
+    assert_eq!(out, b"[]\n");
+
+    assert_eq!(
+        ExportFormat::from_path("out.JSONL"),
+        Some(ExportFormat::Ndjson)
+    );
+    assert!(matches!(
+        "xml".parse::<ExportFormat>(),
+        Err(PrqliteError::Export(_))
+    ));
+}
+
+#[test]
+fn test_register_file_table() {
+    let dir = std::env::temp_dir().join(format!("prqlite_files_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let sales = dir.join("sales.csv");
+    std::fs::write(&sales, "region,amount\neu,10\nus,2.5\neu,4\n").unwrap();
+    let events = dir.join("events.ndjson");
+    std::fs::write(
+        &events,
+        "{\"id\": 1, \"kind\": \"click\", \"tags\": [\"a\"]}\n\
+         \n\
+         {\"id\": 2, \"kind\": null, \"extra\": true}\n\
+         not json\n",
+    )
+    .unwrap();
+    let users = dir.join("users.json");
+    std::fs::write(
+        &users,
+        "[{\"name\": \"ann\", \"age\": 31}, {\"name\": \"bo\"}]",
+    )
+    .unwrap();
+
+    let conn = Prqlite::builder().declare_schema(true).open().unwrap();
+    let format = FileTableFormat::from_path(&sales).unwrap();
+    conn.register_file_table("sales", &sales, &format).unwrap();
+    let rows = conn
+        .query("from sales | group region (aggregate [total = sum amount]) | sort region")
+        .unwrap();
+    assert_eq!(
+        rows.rows,
+        vec![
+            vec![Value::Text("eu".to_owned()), Value::Real(14.0)],
+            vec![Value::Text("us".to_owned()), Value::Real(2.5)]
+        ]
+    );
+
+    let report = conn
+        .register_file_table("events", &events, &FileTableFormat::Ndjson)
+        .unwrap();
+    assert_eq!(
+        report.columns,
+        [
+            ("id", "INTEGER"),
+            ("kind", "TEXT"),
+            ("tags", "TEXT"),
+            ("extra", "INTEGER")
+        ]
+        .map(|(name, ty)| (name.to_owned(), ty.to_owned()))
+    );
+    assert_eq!(report.inserted, 2);
+    assert_eq!(report.rejected.len(), 1);
+    assert_eq!(report.rejected[0].line, 4);
+    let rows = conn
+        .query("from events | filter id == 1 | select [tags, extra]")
+        .unwrap();
+    assert_eq!(
+        rows.rows[0],
+        vec![Value::Text("[\"a\"]".to_owned()), Value::Null]
+    );
+
+    let format = FileTableFormat::from_path(&users).unwrap();
+    conn.register_file_table("users", &users, &format).unwrap();
+    assert_eq!(
+        conn.query("from users | filter age == null").unwrap().rows,
+        vec![vec![Value::Text("bo".to_owned()), Value::Null]]
+    );
+
+    // A file that can't be loaded leaves the registered table in place.
+    std::fs::write(&users, "[{\"name\": \"cy\"},").unwrap();
+    assert!(matches!(
+        conn.register_file_table("users", &users, &format),
+        Err(PrqliteError::Import(_))
+    ));
+    assert_eq!(conn.query("from users").unwrap().len(), 2);
+
+    // Registering again re-reads the file.
+    std::fs::write(&sales, "region,amount\neu,1\n").unwrap();
+    let format = FileTableFormat::from_path(&sales).unwrap();
+    conn.register_file_table("sales", &sales, &format).unwrap();
+    assert_eq!(conn.query("from sales").unwrap().len(), 1);
+    assert!(!conn
+        .schema()
+        .unwrap()
+        .database("main")
+        .unwrap()
+        .tables
+        .iter()
+        .any(|t| t.name == "sales"));
+
+    assert!(matches!(
+        conn.register_file_table("missing", dir.join("missing.csv"), &format),
+        Err(PrqliteError::Import(_))
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -4,9 +4,9 @@ use std::{
     fs::{remove_file, File},
     path::Path,
 };
-type TYPE = Vec<(i32, String, String)>;
-pub fn generate_fake_data(i: i32) -> TYPE {
-    let mut data: TYPE = vec![];
+type FakeData = Vec<(i32, String, String)>;
+pub fn generate_fake_data(i: i32) -> FakeData {
+    let mut data: FakeData = vec![];
     let rng = rand::thread_rng();
 
     for _ in 0..i {
@@ -27,7 +27,7 @@ pub fn generate_fake_data(i: i32) -> TYPE {
     data
 }
 
-pub fn reset_database(db_path: &str, data: TYPE) {
+pub fn reset_database(db_path: &str, data: FakeData) {
     if Path::new(db_path).exists() {
         remove_file(db_path).unwrap();
     }
@@ -47,12 +47,10 @@ pub fn reset_database(db_path: &str, data: TYPE) {
         Err(err) => println!("error : {err}"),
     };
 
-    for (id, name, address) in data.clone() {
-        let sql = &format!(
-            "INSERT INTO persons VALUES ('{}', '{}', '{}')",
-            id, name, address,
-        );
-        let mut stmt = conn.execute_with_sql(sql).unwrap();
-        stmt.execute([]).unwrap();
+    let mut stmt = conn
+        .execute_with_sql("INSERT INTO persons VALUES (?1, ?2, ?3)")
+        .unwrap();
+    for (id, name, address) in data {
+        stmt.execute(params![id, name, address]).unwrap();
     }
 }
@@ -0,0 +1,109 @@
+use crate::{Prqlite, Result};
+use rusqlite::TransactionBehavior;
+use std::ops::Deref;
+
+/// Guard of a transaction, or of a savepoint nested in one, created by
+/// [`Prqlite::transaction`] and [`Transaction::savepoint`].
+///
+/// It dereferences to [`Prqlite`], so PRQL and SQL are executed through the usual
+/// `execute*` methods. Changes are rolled back when the guard is dropped without
+/// calling [`Transaction::commit`].
+pub struct Transaction<'a> {
+    prqlite: &'a Prqlite,
+    savepoint: Option<String>,
+    depth: usize,
+    finished: bool,
+}
+
+impl<'a> Transaction<'a> {
+    pub(crate) fn begin(prqlite: &'a Prqlite, behavior: TransactionBehavior) -> Result<Self> {
+        let sql = match behavior {
+            TransactionBehavior::Immediate => "BEGIN IMMEDIATE",
+            TransactionBehavior::Exclusive => "BEGIN EXCLUSIVE",
+            _ => "BEGIN DEFERRED",
+        };
+        prqlite.execute_batch_with_sql(sql)?;
+        Ok(Self {
+            prqlite,
+            savepoint: None,
+            depth: 0,
+            finished: false,
+        })
+    }
+
+    /// Start a savepoint directly on the connection: nested in the transaction the caller
+    /// may have open, or acting as a deferred transaction otherwise.
+    pub(crate) fn begin_savepoint(prqlite: &'a Prqlite, name: &str) -> Result<Self> {
+        prqlite.execute_batch_with_sql(&format!("SAVEPOINT {name}"))?;
+        Ok(Self {
+            prqlite,
+            savepoint: Some(name.to_owned()),
+            depth: 0,
+            finished: false,
+        })
+    }
+
+    /// Start a savepoint inside this transaction. The parent can't be used until the
+    /// savepoint is committed (released) or rolled back.
+    pub fn savepoint(&mut self) -> Result<Transaction<'_>> {
+        let depth = self.depth + 1;
+        let name = format!("prqlite_sp_{depth}");
+        self.prqlite
+            .execute_batch_with_sql(&format!("SAVEPOINT {name}"))?;
+        Ok(Transaction {
+            prqlite: self.prqlite,
+            savepoint: Some(name),
+            depth,
+            finished: false,
+        })
+    }
+
+    /// Whether this guard is a savepoint rather than the outermost transaction.
+    pub fn is_savepoint(&self) -> bool {
+        self.savepoint.is_some()
+    }
+
+    /// Commit the transaction, or release the savepoint. When it fails, e.g. because
+    /// the database is busy, the changes are rolled back as the guard is dropped.
+    pub fn commit(mut self) -> Result<()> {
+        match &self.savepoint {
+            Some(name) => self
+                .prqlite
+                .execute_batch_with_sql(&format!("RELEASE {name}"))?,
+            None => self.prqlite.execute_batch_with_sql("COMMIT")?,
+        }
+        self.finished = true;
+        Ok(())
+    }
+
+    pub fn rollback(mut self) -> Result<()> {
+        self.rollback_()?;
+        self.finished = true;
+        Ok(())
+    }
+
+    fn rollback_(&self) -> Result<()> {
+        match &self.savepoint {
+            Some(name) => self
+                .prqlite
+                .execute_batch_with_sql(&format!("ROLLBACK TO {name}; RELEASE {name}")),
+            None => self.prqlite.execute_batch_with_sql("ROLLBACK"),
+        }
+    }
+}
+
+impl Deref for Transaction<'_> {
+    type Target = Prqlite;
+
+    fn deref(&self) -> &Prqlite {
+        self.prqlite
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.rollback_();
+        }
+    }
+}